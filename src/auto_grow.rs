@@ -0,0 +1,237 @@
+//! The `auto_grow` module packs boxes into a single bin that starts small and grows until
+//! everything fits, which finds a tight atlas size without guessing bin dimensions up front.
+
+use crate::bucket::Bucket;
+use crate::max_rects::MaxRects;
+use crate::packing_box::PackingBox;
+
+/// How the bin grows after an attempt leaves boxes unplaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Growth {
+    /// Doubles the shorter side, rounding it up to a power of two.
+    #[default]
+    PowerOfTwo,
+    /// Adds the given amount to both sides.
+    Step(i32),
+    /// Adds the given amount to the shorter side only, so the bin tends towards a square.
+    TowardSquare(i32),
+}
+
+impl Growth {
+    /// Returns the next bin size after `width` by `height`, clamped to the maximum size.
+    ///
+    /// When one side has reached its maximum the other side grows instead. A side that would
+    /// pass `i32::MAX` stops at its maximum.
+    fn next(&self, width: i32, height: i32, max_width: i32, max_height: i32) -> (i32, i32) {
+        let grow = |side: i32| match self {
+            Growth::PowerOfTwo => {
+                let mut size: i32 = 1;
+                while size <= side {
+                    match size.checked_mul(2) {
+                        Some(doubled) => size = doubled,
+                        None => return i32::MAX,
+                    }
+                }
+                size
+            }
+            Growth::Step(step) | Growth::TowardSquare(step) => {
+                side.saturating_add(i32::max(*step, 1))
+            }
+        };
+
+        if let Growth::Step(_) = self {
+            return (
+                i32::min(grow(width), max_width),
+                i32::min(grow(height), max_height),
+            );
+        }
+
+        let widen = (width <= height && width < max_width) || height >= max_height;
+        if widen {
+            (i32::min(grow(width), max_width), height)
+        } else {
+            (width, i32::min(grow(height), max_height))
+        }
+    }
+}
+
+/// An `AutoGrow` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bin`: The bin to start from. After `place` it holds the size that was used.
+/// - `max_width`: The largest width the bin may grow to.
+/// - `max_height`: The largest height the bin may grow to.
+/// - `growth`: How the bin grows between attempts.
+/// - `packer`: The template for every attempt, see [`MaxRects::renew`].
+#[derive(Clone)]
+pub struct AutoGrow {
+    pub boxes: Vec<PackingBox>,
+    pub bin: Bucket,
+    pub max_width: i32,
+    pub max_height: i32,
+    pub growth: Growth,
+    pub packer: MaxRects,
+}
+
+impl AutoGrow {
+    /// Constructs a new `AutoGrow` instance that grows by powers of two.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bin`: The initial bin.
+    /// - `max_width`: The largest width the bin may grow to.
+    /// - `max_height`: The largest height the bin may grow to.
+    ///
+    /// # Returns
+    /// A new `AutoGrow` object.
+    pub fn new(boxes: Vec<PackingBox>, bin: Bucket, max_width: i32, max_height: i32) -> Self {
+        Self {
+            boxes,
+            bin,
+            max_width,
+            max_height,
+            growth: Growth::default(),
+            packer: MaxRects::new(vec![], vec![]),
+        }
+    }
+    /// Sets how the bin grows between attempts.
+    pub fn with_growth(mut self, growth: Growth) -> Self {
+        self.growth = growth;
+        self
+    }
+    /// Sets the template for every attempt.
+    pub fn with_packer(mut self, packer: MaxRects) -> Self {
+        self.packer = packer;
+        self
+    }
+    /// Packs the boxes, growing the bin and packing again for as long as boxes are left over
+    /// and the bin is below its maximum size.
+    ///
+    /// Sizes whose area is smaller than the total area of the boxes are skipped without
+    /// packing. `bin` is updated to the size of the final attempt.
+    ///
+    /// # Returns
+    /// A tuple of three vectors from the final attempt:
+    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
+    /// - A vector of `Bucket` objects representing the free space left in the bin.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{auto_grow::AutoGrow, bucket::Bucket, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(30, 30); 5];
+    /// let mut atlas = AutoGrow::new(boxes, Bucket::new(16, 16, 0, 0, 0), 1024, 1024);
+    /// let (placed, unplaced, _) = atlas.place();
+    ///
+    /// assert_eq!(placed.len(), 5);
+    /// assert!(unplaced.is_empty());
+    /// assert_eq!((atlas.bin.width, atlas.bin.height), (128, 64));
+    /// ```
+    pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        let box_area: i64 = self
+            .boxes
+            .iter()
+            .map(|b| b.width as i64 * b.height as i64)
+            .sum();
+        self.bin.width = i32::min(self.bin.width, self.max_width);
+        self.bin.height = i32::min(self.bin.height, self.max_height);
+
+        loop {
+            let (width, height) = (self.bin.width, self.bin.height);
+            let at_maximum = width >= self.max_width && height >= self.max_height;
+
+            if self.bin.area() >= box_area || at_maximum {
+                let mut attempt = self
+                    .packer
+                    .renew(self.boxes.clone(), vec![self.bin.clone()]);
+                let result = attempt.place();
+                if result.1.is_empty() || at_maximum {
+                    return result;
+                }
+            }
+
+            let (next_width, next_height) =
+                self.growth
+                    .next(width, height, self.max_width, self.max_height);
+            self.bin.width = next_width;
+            self.bin.height = next_height;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristic::Heuristic;
+
+    #[test]
+    fn test_growth_sizes() {
+        assert_eq!(Growth::PowerOfTwo.next(16, 16, 1024, 1024), (32, 16));
+        assert_eq!(Growth::PowerOfTwo.next(32, 16, 1024, 1024), (32, 32));
+        assert_eq!(Growth::PowerOfTwo.next(20, 40, 1024, 1024), (32, 40));
+        assert_eq!(Growth::Step(10).next(20, 40, 1024, 1024), (30, 50));
+        assert_eq!(Growth::TowardSquare(10).next(20, 40, 1024, 1024), (30, 40));
+        // A side at its maximum stops growing and the other side takes over.
+        assert_eq!(Growth::PowerOfTwo.next(64, 128, 64, 1024), (64, 256));
+        assert_eq!(Growth::Step(10).next(60, 100, 64, 105), (64, 105));
+        // Growth past `i32::MAX` stops at the maximum instead of overflowing.
+        let max = i32::MAX;
+        assert_eq!(
+            Growth::PowerOfTwo.next(1 << 30, 1 << 30, max, max),
+            (max, 1 << 30)
+        );
+        assert_eq!(Growth::Step(10).next(max - 5, 10, max, max), (max, 20));
+    }
+
+    #[test]
+    fn test_place_large_boxes() {
+        let boxes = vec![PackingBox::new(40_000, 40_000); 2];
+        let mut auto_grow = AutoGrow::new(boxes, Bucket::new(16, 16, 0, 0, 0), 100_000, 100_000);
+        let (placed, unplaced, _) = auto_grow.place();
+
+        assert_eq!(placed.len(), 2);
+        assert!(unplaced.is_empty());
+        assert_eq!(
+            (auto_grow.bin.width, auto_grow.bin.height),
+            (100_000, 65_536)
+        );
+    }
+
+    #[test]
+    fn test_place_grows_until_everything_fits() {
+        let boxes = vec![PackingBox::new(10, 10); 6];
+        let mut auto_grow = AutoGrow::new(boxes, Bucket::new(10, 10, 0, 0, 3), 100, 100)
+            .with_growth(Growth::TowardSquare(10));
+        let (placed, unplaced, _) = auto_grow.place();
+
+        assert_eq!(placed.len(), 6);
+        assert!(unplaced.is_empty());
+        assert_eq!((auto_grow.bin.width, auto_grow.bin.height), (30, 20));
+        assert!(placed.iter().all(|b| b.bucketid == Some(3)));
+    }
+
+    #[test]
+    fn test_place_stops_at_maximum() {
+        let boxes = vec![PackingBox::new(10, 10); 6];
+        let mut auto_grow = AutoGrow::new(boxes, Bucket::new(8, 8, 0, 0, 0), 20, 20);
+        let (placed, unplaced, _) = auto_grow.place();
+
+        assert_eq!(placed.len(), 4);
+        assert_eq!(unplaced.len(), 2);
+        assert_eq!((auto_grow.bin.width, auto_grow.bin.height), (20, 20));
+    }
+
+    #[test]
+    fn test_place_uses_packer_settings() {
+        let boxes = vec![PackingBox::new(40, 10)];
+        let packer = MaxRects::new(vec![], vec![])
+            .with_rotation(true)
+            .with_heuristic(Heuristic::BestAreaFit);
+        let mut auto_grow =
+            AutoGrow::new(boxes, Bucket::new(16, 16, 0, 0, 0), 16, 64).with_packer(packer);
+        let (placed, unplaced, _) = auto_grow.place();
+
+        assert!(unplaced.is_empty());
+        assert!(placed[0].rotated);
+    }
+}
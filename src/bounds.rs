@@ -0,0 +1,463 @@
+//! The `bounds` module computes lower bounds on the number of identical bins needed for a set
+//! of boxes, following Martello and Vigo's L1, L2 and L3 bounds for two-dimensional bin
+//! packing, together with a grid-counting bound. Comparing a packing against the bound shows
+//! whether a poor result is the algorithm's fault or the instance's. `MaxRects::pack`, the
+//! portfolio and the optimizer report this gap with their results.
+//!
+//! The bounds assume every box fits into the bin and keeps its orientation. When rotation is
+//! allowed each box is replaced by the square on its shorter side, which any orientation of
+//! the box contains, so the bounds stay valid but become weaker.
+
+use crate::bucket::Bucket;
+use crate::packing_box::{Orientation, PackingBox};
+
+/// The width and height of a box, widened to avoid overflow in area sums.
+type Size = (i64, i64);
+
+/// A `LowerBounds` object contains the following fields:
+/// - `continuous`: The total box area divided by the bin area, rounded up.
+/// - `l1`: The larger of the one-dimensional bounds on boxes too wide, or too tall, to sit
+///   side by side.
+/// - `l2`: `l1` improved by the area of boxes that cannot share a bin with the largest ones.
+/// - `l3`: `l2` applied after fixing the bins whose contents are forced, plus those bins.
+/// - `grid`: `l2` improved by counting how many boxes of at least a given size fit in a bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowerBounds {
+    pub continuous: usize,
+    pub l1: usize,
+    pub l2: usize,
+    pub l3: usize,
+    pub grid: usize,
+}
+
+/// The distance between a packing and the lower bound.
+///
+/// - `bins_used`: The number of bins the packing used.
+/// - `lower_bound`: The best lower bound.
+/// - `absolute`: How many bins more than the bound were used.
+/// - `relative`: `absolute` as a fraction of the bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gap {
+    pub bins_used: usize,
+    pub lower_bound: usize,
+    pub absolute: usize,
+    pub relative: f64,
+}
+
+impl LowerBounds {
+    /// Computes every bound for `boxes` in bins the size of `bin`.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bounds::LowerBounds, bucket::Bucket, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(4, 4); 5];
+    /// let bounds = LowerBounds::new(&boxes, &Bucket::new(10, 10, 0, 0, 0), false);
+    ///
+    /// assert_eq!(bounds.continuous, 1);
+    /// assert_eq!(bounds.best(), 2);
+    /// assert_eq!(bounds.gap(3).absolute, 1);
+    /// ```
+    pub fn new(boxes: &[PackingBox], bin: &Bucket, allow_rotation: bool) -> Self {
+        let sizes: Vec<Size> = boxes
+            .iter()
+            .map(|b| match allow_rotation {
+                true => {
+                    let side = i32::min(b.width, b.height) as i64;
+                    (side, side)
+                }
+                false => (b.width as i64, b.height as i64),
+            })
+            .collect();
+        let (width, height) = (bin.width as i64, bin.height as i64);
+
+        let continuous = continuous(boxes, bin);
+        let l1 = l1(&sizes, width, height);
+        let l2 = usize::max(l1, l2(&sizes, width, height));
+        let l3 = usize::max(l2, l3(&sizes, width, height));
+        let grid = usize::max(l2, grid(&sizes, width, height));
+        Self {
+            continuous,
+            l1,
+            l2,
+            l3,
+            grid,
+        }
+    }
+    /// Computes every bound for a packer's boxes and bins, counting a box as turnable when
+    /// rotation is allowed or its orientation follows the grain.
+    ///
+    /// # Returns
+    /// The bounds, or `None` if there are no bins or the bins differ in size.
+    pub fn for_bins(boxes: &[PackingBox], bins: &[Bucket], allow_rotation: bool) -> Option<Self> {
+        let bin = bins.first()?;
+        if bins
+            .iter()
+            .any(|b| (b.width, b.height) != (bin.width, bin.height))
+        {
+            return None;
+        }
+        let turns = allow_rotation
+            || boxes
+                .iter()
+                .any(|b| matches!(b.orientation, Orientation::Grain(_)));
+        Some(Self::new(boxes, bin, turns))
+    }
+    /// Returns the strongest of the bounds.
+    pub fn best(&self) -> usize {
+        self.continuous
+            .max(self.l1)
+            .max(self.l2)
+            .max(self.l3)
+            .max(self.grid)
+    }
+    /// Measures a packing that used `bins_used` bins against the strongest bound.
+    pub fn gap(&self, bins_used: usize) -> Gap {
+        let lower_bound = self.best();
+        let absolute = bins_used.saturating_sub(lower_bound);
+        let relative = match lower_bound {
+            0 => 0.0,
+            _ => absolute as f64 / lower_bound as f64,
+        };
+        Gap {
+            bins_used,
+            lower_bound,
+            absolute,
+            relative,
+        }
+    }
+}
+
+/// Returns the total area of `boxes` divided by the area of `bin`, rounded up.
+pub fn continuous(boxes: &[PackingBox], bin: &Bucket) -> usize {
+    let area: i64 = boxes.iter().map(|b| b.width as i64 * b.height as i64).sum();
+    let bin_area = bin.width as i64 * bin.height as i64;
+    if bin_area <= 0 {
+        return 0;
+    }
+    ((area + bin_area - 1) / bin_area) as usize
+}
+
+/// Divides `a` by `b`, rounding up, and clamps negative results to 0.
+fn ceil_div(a: i64, b: i64) -> usize {
+    if a <= 0 || b <= 0 {
+        0
+    } else {
+        ((a + b - 1) / b) as usize
+    }
+}
+
+/// Returns the thresholds in `1..=limit / 2` at which the sets of items used by the bounds
+/// change, given the item lengths along one side of length `limit`.
+fn thresholds(lengths: impl Iterator<Item = i64>, limit: i64) -> Vec<i64> {
+    let mut values = vec![1];
+    for length in lengths {
+        values.push(length);
+        values.push(length + 1);
+        values.push(limit - length + 1);
+    }
+    values.retain(|&v| v >= 1 && 2 * v <= limit);
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// Bounds the bins needed by items longer than half the bin along `across`. No two of them
+/// fit side by side, so they form a one-dimensional problem along `along`, which is bounded
+/// with Martello and Toth's L2 bound.
+fn l1_along(sizes: &[Size], across_limit: i64, along_limit: i64) -> usize {
+    let lengths: Vec<i64> = sizes
+        .iter()
+        .filter(|&&(across, _)| 2 * across > across_limit)
+        .map(|&(_, along)| along)
+        .collect();
+
+    let mut alphas = thresholds(lengths.iter().copied(), along_limit);
+    alphas.push(0);
+    alphas
+        .into_iter()
+        .map(|alpha| {
+            let big = lengths.iter().filter(|&&l| l > along_limit - alpha).count();
+            let medium: Vec<i64> = lengths
+                .iter()
+                .copied()
+                .filter(|&l| l <= along_limit - alpha && 2 * l > along_limit)
+                .collect();
+            let small: i64 = lengths
+                .iter()
+                .filter(|&&l| 2 * l <= along_limit && l >= alpha)
+                .sum();
+            let room = medium.len() as i64 * along_limit - medium.iter().sum::<i64>();
+            big + medium.len() + ceil_div(small - room, along_limit)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Martello and Vigo's L1: the larger of the bounds on the items wider than half the bin and
+/// on the items taller than half the bin.
+fn l1(sizes: &[Size], width: i64, height: i64) -> usize {
+    let transposed: Vec<Size> = sizes.iter().map(|&(w, h)| (h, w)).collect();
+    usize::max(
+        l1_along(sizes, width, height),
+        l1_along(&transposed, height, width),
+    )
+}
+
+/// Splits the items for the thresholds `q` across the width and `p` across the height into
+/// the items no other of these items can share a bin with, the remaining items larger than
+/// half the bin in both directions, and the items at least `q` by `p` that are no larger than
+/// half the bin in both directions.
+fn classify(
+    sizes: &[Size],
+    width: i64,
+    height: i64,
+    q: i64,
+    p: i64,
+) -> (usize, Vec<Size>, Vec<Size>) {
+    let mut alone = 0;
+    let mut large = vec![];
+    let mut small = vec![];
+    for &(w, h) in sizes {
+        if w > width - q && h > height - p {
+            alone += 1;
+        } else if 2 * w > width && 2 * h > height {
+            large.push((w, h));
+        } else if 2 * w <= width && 2 * h <= height && w >= q && h >= p {
+            small.push((w, h));
+        }
+    }
+    (alone, large, small)
+}
+
+/// Martello and Vigo's L2 bound, evaluated at the thresholds where the item sets change.
+///
+/// Items of the first set cannot share a bin with any item of at least `q` by `p`, and the
+/// large items cannot share a bin with each other, so the small items must fit in the area
+/// left over in the bins of the large items and in additional bins.
+fn l2(sizes: &[Size], width: i64, height: i64) -> usize {
+    let qs = thresholds(sizes.iter().map(|s| s.0), width);
+    let ps = thresholds(sizes.iter().map(|s| s.1), height);
+    let bin_area = width * height;
+
+    let mut best = 0;
+    for &q in &qs {
+        for &p in &ps {
+            let (alone, large, small) = classify(sizes, width, height, q, p);
+            let area: i64 = large.iter().chain(&small).map(|(w, h)| w * h).sum();
+            let bound =
+                alone + large.len() + ceil_div(area - large.len() as i64 * bin_area, bin_area);
+            best = best.max(bound);
+        }
+    }
+    best
+}
+
+/// Martello and Vigo's L3: bins whose contents are forced are fixed one at a time, and `l1`
+/// and `l2` bound the items that are left.
+///
+/// An item that no other item can share a bin with fills a bin on its own. An item that can
+/// share a bin with at most one other item at a time, where one of those partners is at
+/// least as wide and as tall as all the others, can be given a bin with that partner: any
+/// packing can be rearranged to contain that bin by swapping the partner in.
+fn l3(sizes: &[Size], width: i64, height: i64) -> usize {
+    let pairs = |a: Size, b: Size| a.0 + b.0 <= width || a.1 + b.1 <= height;
+    let area = |s: Size| s.0 * s.1;
+    let mut items = sizes.to_vec();
+    items.sort_by_key(|&s| std::cmp::Reverse(area(s)));
+
+    let mut fixed = 0;
+    let mut changed = true;
+    while std::mem::take(&mut changed) {
+        let mut j = 0;
+        while j < items.len() {
+            let item = items[j];
+            let partners: Vec<Size> = items
+                .iter()
+                .enumerate()
+                .filter(|&(k, &other)| k != j && pairs(item, other))
+                .map(|(_, &other)| other)
+                .collect();
+            // No two partners fit in the bin together with the item.
+            let exclusive = partners.iter().enumerate().all(|(i, &a)| {
+                partners[i + 1..]
+                    .iter()
+                    .all(|&b| !pairs(a, b) || area(item) + area(a) + area(b) > width * height)
+            });
+            let partner = partners
+                .iter()
+                .copied()
+                .find(|&a| partners.iter().all(|&b| b.0 <= a.0 && b.1 <= a.1));
+
+            match (exclusive, partners.is_empty(), partner) {
+                (true, true, _) => {
+                    items.remove(j);
+                }
+                (true, false, Some(partner)) => {
+                    items.remove(j);
+                    let k = items.iter().position(|&s| s == partner).unwrap();
+                    items.remove(k);
+                    if k < j {
+                        j -= 1;
+                    }
+                }
+                _ => {
+                    j += 1;
+                    continue;
+                }
+            }
+            fixed += 1;
+            // Removing items can leave earlier ones with fewer partners, so scan again.
+            changed = true;
+        }
+    }
+    fixed + usize::max(l1(&items, width, height), l2(&items, width, height))
+}
+
+/// A count-based bound on the number of grid points each bin offers.
+///
+/// At most `(width / q) * (height / p)` items of at least `q` by `p` fit in a bin, because
+/// each contains a distinct point of a grid with those spacings. A large item covers some of
+/// those points, leaving fewer for small items in its bin.
+fn grid(sizes: &[Size], width: i64, height: i64) -> usize {
+    let qs = thresholds(sizes.iter().map(|s| s.0), width);
+    let ps = thresholds(sizes.iter().map(|s| s.1), height);
+
+    let mut best = 0;
+    for &q in &qs {
+        for &p in &ps {
+            let (alone, large, small) = classify(sizes, width, height, q, p);
+            let per_bin = (width / q) * (height / p);
+            let room: i64 = large
+                .iter()
+                .map(|&(w, h)| per_bin - (w / q) * (h / p))
+                .sum();
+            let bound = alone + large.len() + ceil_div(small.len() as i64 - room, per_bin);
+            best = best.max(bound);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bin() -> Bucket {
+        Bucket::new(10, 10, 0, 0, 0)
+    }
+
+    #[test]
+    fn test_continuous() {
+        assert_eq!(continuous(&[], &bin()), 0);
+        assert_eq!(continuous(&vec![PackingBox::new(5, 5); 4], &bin()), 1);
+        assert_eq!(continuous(&vec![PackingBox::new(5, 5); 5], &bin()), 2);
+    }
+
+    #[test]
+    fn test_l1_beats_continuous() {
+        let boxes = vec![PackingBox::new(6, 6); 3];
+        let bounds = LowerBounds::new(&boxes, &bin(), false);
+
+        assert_eq!(bounds.continuous, 2);
+        assert_eq!(bounds.l1, 3);
+        assert_eq!(bounds.best(), 3);
+    }
+
+    #[test]
+    fn test_l1_one_dimensional() {
+        // Wide strips of height 4, 4 and 3 need two bins stacked along the height.
+        let boxes = vec![
+            PackingBox::new(8, 4),
+            PackingBox::new(8, 4),
+            PackingBox::new(8, 3),
+        ];
+        let bounds = LowerBounds::new(&boxes, &bin(), false);
+        assert_eq!(bounds.continuous, 1);
+        assert_eq!(bounds.l1, 2);
+    }
+
+    #[test]
+    fn test_l2_beats_l1() {
+        let mut boxes = vec![PackingBox::new(7, 7); 4];
+        boxes.extend(vec![PackingBox::new(4, 4); 4]);
+        let bounds = LowerBounds::new(&boxes, &bin(), false);
+
+        assert_eq!(bounds.continuous, 3);
+        assert_eq!(bounds.l1, 4);
+        assert_eq!(bounds.l2, 5);
+    }
+
+    #[test]
+    fn test_grid_beats_l2() {
+        let boxes = vec![PackingBox::new(4, 4); 5];
+        let bounds = LowerBounds::new(&boxes, &bin(), false);
+
+        assert_eq!(bounds.l2, 1);
+        assert_eq!(bounds.grid, 2);
+    }
+
+    #[test]
+    fn test_l3_beats_l2() {
+        // The 10x3 box can only share its bin with the 1x1 box, so that bin is fixed and the
+        // 1x8 box needs another.
+        let boxes = vec![
+            PackingBox::new(10, 3),
+            PackingBox::new(1, 8),
+            PackingBox::new(1, 1),
+        ];
+        let bounds = LowerBounds::new(&boxes, &bin(), false);
+
+        assert_eq!(bounds.l2, 1);
+        assert_eq!(bounds.grid, 1);
+        assert_eq!(bounds.l3, 2);
+        assert_eq!(bounds.best(), 2);
+    }
+
+    #[test]
+    fn test_for_bins() {
+        let boxes = vec![PackingBox::new(8, 3); 2];
+        let bins = vec![Bucket::new(10, 5, 0, 0, 0), Bucket::new(10, 5, 0, 0, 1)];
+
+        assert_eq!(
+            LowerBounds::for_bins(&boxes, &bins, false).unwrap().best(),
+            2
+        );
+        assert_eq!(
+            LowerBounds::for_bins(&boxes, &bins, true).unwrap().best(),
+            1
+        );
+        assert!(LowerBounds::for_bins(&boxes, &[], false).is_none());
+        let mixed = vec![Bucket::new(10, 5, 0, 0, 0), Bucket::new(5, 10, 0, 0, 1)];
+        assert!(LowerBounds::for_bins(&boxes, &mixed, false).is_none());
+    }
+
+    #[test]
+    fn test_rotation_weakens_bounds() {
+        let boxes = vec![PackingBox::new(8, 3); 2];
+        let bin = Bucket::new(10, 5, 0, 0, 0);
+
+        assert_eq!(LowerBounds::new(&boxes, &bin, false).best(), 2);
+        assert_eq!(LowerBounds::new(&boxes, &bin, true).best(), 1);
+    }
+
+    #[test]
+    fn test_gap() {
+        let bounds = LowerBounds {
+            continuous: 2,
+            l1: 3,
+            l2: 3,
+            l3: 3,
+            grid: 3,
+        };
+        let gap = bounds.gap(4);
+        assert_eq!((gap.bins_used, gap.lower_bound, gap.absolute), (4, 3, 1));
+        assert!((gap.relative - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(bounds.gap(2).absolute, 0);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!(thresholds([3, 8].into_iter(), 10), vec![1, 3, 4]);
+    }
+}
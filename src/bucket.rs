@@ -13,6 +13,11 @@
 /// assert_eq!(bucket.originy, 5);
 /// assert_eq!(bucket.bucketid, 1);
 /// ```
+///
+/// `grain` optionally records the direction of the material's grain, which boxes with an
+/// `Orientation::Grain` policy line up with. `obstacles` are regions inside the bucket that
+/// are already taken, such as clamps, defects or reserved areas, which no packer places
+/// boxes over.
 #[derive(Debug, Clone)]
 pub struct Bucket {
     pub width: i32,
@@ -20,6 +25,27 @@ pub struct Bucket {
     pub originx: i32,
     pub originy: i32,
     pub bucketid: i32,
+    pub grain: Option<GrainAxis>,
+    pub obstacles: Vec<Bucket>,
+}
+
+/// The direction a grain runs in, relative to the x and y axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrainAxis {
+    /// The grain runs along the width.
+    Horizontal,
+    /// The grain runs along the height.
+    Vertical,
+}
+
+impl GrainAxis {
+    /// Returns the axis after a 90 degree turn.
+    pub fn turned(self) -> Self {
+        match self {
+            GrainAxis::Horizontal => GrainAxis::Vertical,
+            GrainAxis::Vertical => GrainAxis::Horizontal,
+        }
+    }
 }
 
 impl Bucket {
@@ -38,8 +64,34 @@ impl Bucket {
             originx,
             originy,
             bucketid,
+            grain: None,
+            obstacles: vec![],
         }
     }
+    /// Sets the direction of the bucket's grain.
+    pub fn with_grain(mut self, grain: GrainAxis) -> Self {
+        self.grain = Some(grain);
+        self
+    }
+    /// Marks a rectangle inside the bucket as taken, so no box is placed over it.
+    ///
+    /// The obstacle uses the same coordinates as the bucket and placed boxes, not offsets
+    /// from the bucket's origin.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let sheet = Bucket::new(10, 10, 0, 0, 0).with_obstacle(2, 2, 4, 4);
+    /// let mut problem = MaxRects::new(vec![PackingBox::new(10, 4)], vec![sheet]);
+    /// let (placed, _, _) = problem.place();
+    /// assert!(!placed[0].overlap(&problem.containers[0].obstacles[0]));
+    /// ```
+    pub fn with_obstacle(mut self, width: i32, height: i32, originx: i32, originy: i32) -> Self {
+        self.obstacles
+            .push(Bucket::new(width, height, originx, originy, self.bucketid));
+        self
+    }
     /// Returns the coordinates of the corners of the bucket.
     ///
     /// The coordinates are returned as a tuple of four `i32` values: `(left, right, top, bottom)`.
@@ -86,15 +138,151 @@ impl Bucket {
             && self_corners[0].1 <= other_corners[0].1
             && other_corners[3].1 <= self_corners[3].1
     }
+    /// Splits the bucket around `used`, returning the largest rectangles left over to its left,
+    /// right, above and below. The pieces overlap each other; empty pieces are left out.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::bucket::Bucket;
+    ///
+    /// let free = Bucket::new(10, 10, 0, 0, 1);
+    /// let pieces = free.split(&Bucket::new(4, 4, 0, 6, 1));
+    /// assert_eq!(pieces.len(), 2);
+    /// assert_eq!(pieces[0].get_coords(), (4, 10, 0, 10));
+    /// assert_eq!(pieces[1].get_coords(), (0, 10, 0, 6));
+    /// ```
+    pub fn split(&self, used: &Bucket) -> Vec<Bucket> {
+        let (x1, x2, y1, y2) = self.get_coords();
+        let (ux1, ux2, uy1, uy2) = used.get_coords();
+        let left = Bucket::new(ux1 - x1, self.height, x1, y1, self.bucketid);
+        let right = Bucket::new(x2 - ux2, self.height, ux2, y1, self.bucketid);
+        let above = Bucket::new(self.width, uy1 - y1, x1, y1, self.bucketid);
+        let below = Bucket::new(self.width, y2 - uy2, x1, uy2, self.bucketid);
+
+        [left, right, above, below]
+            .into_iter()
+            .filter(|piece| piece.width > 0 && piece.height > 0)
+            .collect()
+    }
+    /// Removes `obstacles` from the bucket, returning the space left as rectangles that do not
+    /// overlap each other. The obstacles are taken to lie in this bucket whatever their
+    /// `bucketid`; empty pieces are left out.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::bucket::Bucket;
+    ///
+    /// let free = Bucket::new(10, 10, 0, 0, 1);
+    /// let pieces = free.subtract(&[Bucket::new(4, 4, 0, 6, 1)]);
+    /// assert_eq!(pieces.len(), 2);
+    /// assert_eq!(pieces[0].get_coords(), (0, 10, 0, 6));
+    /// assert_eq!(pieces[1].get_coords(), (4, 10, 6, 10));
+    /// ```
+    pub fn subtract(&self, obstacles: &[Bucket]) -> Vec<Bucket> {
+        let mut pieces = vec![Bucket {
+            obstacles: vec![],
+            ..self.clone()
+        }];
+        for obstacle in obstacles {
+            let obstacle = Bucket {
+                bucketid: self.bucketid,
+                ..obstacle.clone()
+            };
+            let (ox1, ox2, oy1, oy2) = obstacle.get_coords();
+            let mut left_over = vec![];
+            for piece in pieces {
+                if !piece.overlap(&obstacle) {
+                    left_over.push(piece);
+                    continue;
+                }
+                let (x1, x2, y1, y2) = piece.get_coords();
+                let (top, bottom) = (i32::max(y1, oy1), i32::min(y2, oy2));
+                let id = piece.bucketid;
+                left_over.extend(
+                    [
+                        Bucket::new(piece.width, oy1 - y1, x1, y1, id),
+                        Bucket::new(piece.width, y2 - oy2, x1, oy2, id),
+                        Bucket::new(ox1 - x1, bottom - top, x1, top, id),
+                        Bucket::new(x2 - ox2, bottom - top, ox2, top, id),
+                    ]
+                    .into_iter()
+                    .filter(|rect| rect.width > 0 && rect.height > 0)
+                    .map(|rect| Bucket {
+                        grain: piece.grain,
+                        ..rect
+                    }),
+                );
+            }
+            pieces = left_over;
+        }
+        pieces
+    }
+    /// Joins the bucket with `other` when together they form a single rectangle, that is when
+    /// they line up along one axis and touch or overlap along the other.
+    ///
+    /// # Returns
+    /// The combined bucket, or `None` if the two cannot be merged.
+    pub fn merge(&self, other: &Bucket) -> Option<Bucket> {
+        if self.bucketid != other.bucketid {
+            return None;
+        }
+        let (x1, x2, y1, y2) = self.get_coords();
+        let (ox1, ox2, oy1, oy2) = other.get_coords();
+
+        if x1 == ox1 && x2 == ox2 && y1 <= oy2 && oy1 <= y2 {
+            let top = i32::min(y1, oy1);
+            Some(Bucket {
+                height: i32::max(y2, oy2) - top,
+                originy: top,
+                ..self.clone()
+            })
+        } else if y1 == oy1 && y2 == oy2 && x1 <= ox2 && ox1 <= x2 {
+            let left = i32::min(x1, ox1);
+            Some(Bucket {
+                width: i32::max(x2, ox2) - left,
+                originx: left,
+                ..self.clone()
+            })
+        } else {
+            None
+        }
+    }
     /// Calculates and returns the area of the bucket.
-    pub fn area(&self) -> i32 {
-        self.width * self.height
+    pub fn area(&self) -> i64 {
+        self.width as i64 * self.height as i64
     }
 }
 
+/// Repeatedly merges pairs of free rectangles that together form a single rectangle, until no
+/// more pairs can be merged.
+///
+/// Long-lived packers fragment their free space as boxes come and go; merging recovers large
+/// free regions.
+///
+/// # Returns
+/// The number of merges performed.
+pub fn merge_adjacent(free: &mut Vec<Bucket>) -> usize {
+    let mut merges = 0;
+    let mut i = 0;
+    while i < free.len() {
+        let merged = (i + 1..free.len()).find_map(|j| free[i].merge(&free[j]).map(|m| (j, m)));
+        match merged {
+            Some((j, bucket)) => {
+                free.remove(j);
+                free[i] = bucket;
+                merges += 1;
+                // The grown rectangle may now line up with one checked earlier.
+                i = 0;
+            }
+            None => i += 1,
+        }
+    }
+    merges
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Bucket;
+    use super::{merge_adjacent, Bucket, GrainAxis};
 
     #[test]
     fn test_bucket_creation() {
@@ -104,6 +292,14 @@ mod tests {
         assert_eq!(bucket.originx, 5);
         assert_eq!(bucket.originy, 5);
         assert_eq!(bucket.bucketid, 1);
+        assert_eq!(bucket.grain, None);
+    }
+
+    #[test]
+    fn test_with_grain() {
+        let bucket = Bucket::new(10, 20, 5, 5, 1).with_grain(GrainAxis::Vertical);
+        assert_eq!(bucket.grain, Some(GrainAxis::Vertical));
+        assert_eq!(GrainAxis::Vertical.turned(), GrainAxis::Horizontal);
     }
 
     #[test]
@@ -153,9 +349,70 @@ mod tests {
         assert!(!bucket1.contains(&bucket2));
     }
 
+    #[test]
+    fn test_split() {
+        let bucket = Bucket::new(10, 10, 0, 0, 1);
+        let pieces = bucket.split(&Bucket::new(2, 2, 4, 4, 1));
+        let coords: Vec<_> = pieces.iter().map(|b| b.get_coords()).collect();
+        assert_eq!(
+            coords,
+            vec![(0, 4, 0, 10), (6, 10, 0, 10), (0, 10, 0, 4), (0, 10, 6, 10)]
+        );
+    }
+
+    #[test]
+    fn test_subtract() {
+        let bucket = Bucket::new(10, 10, 0, 0, 1);
+        // The obstacle's id does not matter, and its part outside the bucket is ignored.
+        let pieces = bucket.subtract(&[Bucket::new(2, 2, 4, 4, 0), Bucket::new(4, 4, 8, 8, 1)]);
+        let coords: Vec<_> = pieces.iter().map(|b| b.get_coords()).collect();
+        assert_eq!(
+            coords,
+            vec![
+                (0, 10, 0, 4),
+                (0, 10, 6, 8),
+                (0, 8, 8, 10),
+                (0, 4, 4, 6),
+                (6, 10, 4, 6)
+            ]
+        );
+        let area: i64 = pieces.iter().map(|b| b.area()).sum();
+        assert_eq!(area, 100 - 4 - 4);
+    }
+
+    #[test]
+    fn test_merge() {
+        let top = Bucket::new(10, 5, 0, 0, 1);
+        let bottom = Bucket::new(10, 5, 0, 5, 1);
+        assert_eq!(top.merge(&bottom).unwrap().get_coords(), (0, 10, 0, 10));
+
+        let right = Bucket::new(4, 5, 10, 0, 1);
+        assert_eq!(top.merge(&right).unwrap().get_coords(), (0, 14, 0, 5));
+
+        assert!(bottom.merge(&right).is_none());
+        assert!(top.merge(&Bucket::new(10, 5, 0, 5, 2)).is_none());
+    }
+
+    #[test]
+    fn test_merge_adjacent() {
+        let mut free = vec![
+            Bucket::new(5, 5, 0, 0, 1),
+            Bucket::new(5, 5, 5, 5, 1),
+            Bucket::new(5, 5, 5, 0, 1),
+            Bucket::new(5, 5, 0, 5, 1),
+            Bucket::new(3, 3, 20, 20, 1),
+        ];
+        assert_eq!(merge_adjacent(&mut free), 3);
+        assert_eq!(free.len(), 2);
+        assert_eq!(free[0].get_coords(), (0, 10, 0, 10));
+    }
+
     #[test]
     fn test_area() {
         let bucket = Bucket::new(10, 20, 5, 5, 1);
         assert_eq!(bucket.area(), 200);
+
+        let sheet = Bucket::new(100_000, 100_000, 0, 0, 1);
+        assert_eq!(sheet.area(), 10_000_000_000);
     }
 }
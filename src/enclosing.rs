@@ -0,0 +1,410 @@
+//! The `enclosing` module searches for the smallest single bin that holds a set of boxes,
+//! using `MaxRects` to check whether each candidate size can be filled.
+
+use crate::bucket::Bucket;
+use crate::max_rects::MaxRects;
+use crate::packing_box::PackingBox;
+
+/// The most widths tried when no fixed width or power-of-two constraint narrows them down.
+const MAX_WIDTHS: usize = 256;
+
+/// An `EnclosingBin` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be enclosed.
+/// - `fixed_width`: When set, only bins of exactly this width are considered.
+/// - `aspect_ratio`: When set to `(w, h)`, only bins whose height is the width scaled by
+///   `h / w` (rounded up) are considered.
+/// - `power_of_two`: Whether both sides must be powers of two.
+/// - `max_side`: The longest side a bin may have. Without it, and below it, sides stop at the
+///   sum of the boxes' longest sides, rounded up to a power of two when `power_of_two` is set.
+/// - `packer`: The template for every feasibility check, see [`MaxRects::renew`].
+#[derive(Clone)]
+pub struct EnclosingBin {
+    pub boxes: Vec<PackingBox>,
+    pub fixed_width: Option<i32>,
+    pub aspect_ratio: Option<(i32, i32)>,
+    pub power_of_two: bool,
+    pub max_side: Option<i32>,
+    pub packer: MaxRects,
+}
+
+impl EnclosingBin {
+    /// Constructs a new `EnclosingBin` instance without any constraints on the bin.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be enclosed.
+    ///
+    /// # Returns
+    /// A new `EnclosingBin` object.
+    pub fn new(boxes: Vec<PackingBox>) -> Self {
+        Self {
+            boxes,
+            fixed_width: None,
+            aspect_ratio: None,
+            power_of_two: false,
+            max_side: None,
+            packer: MaxRects::new(vec![], vec![]),
+        }
+    }
+    /// Only considers bins of exactly this width.
+    pub fn with_fixed_width(mut self, width: i32) -> Self {
+        self.fixed_width = Some(width);
+        self
+    }
+    /// Only considers bins whose sides have the ratio `width` to `height`.
+    pub fn with_aspect_ratio(mut self, width: i32, height: i32) -> Self {
+        self.aspect_ratio = Some((width, height));
+        self
+    }
+    /// Requires both sides of the bin to be powers of two.
+    pub fn with_power_of_two(mut self, power_of_two: bool) -> Self {
+        self.power_of_two = power_of_two;
+        self
+    }
+    /// Limits the length of either side of the bin.
+    pub fn with_max_side(mut self, max_side: i32) -> Self {
+        self.max_side = Some(max_side);
+        self
+    }
+    /// Sets the template for every feasibility check.
+    pub fn with_packer(mut self, packer: MaxRects) -> Self {
+        self.packer = packer;
+        self
+    }
+    /// Packs the boxes into a `width` by `height` bin.
+    ///
+    /// # Returns
+    /// The placed boxes if every box fits, otherwise `None`.
+    fn pack(&self, width: i32, height: i32) -> Option<Vec<PackingBox>> {
+        let bin = Bucket::new(width, height, 0, 0, 0);
+        let (placed, unplaced, _) = self.packer.renew(self.boxes.clone(), vec![bin]).place();
+        if unplaced.is_empty() {
+            Some(placed)
+        } else {
+            None
+        }
+    }
+    /// Returns the heights worth trying for a bin of the given width, shortest first.
+    fn heights(&self, width: i32, min_height: i32, max_height: i32) -> Vec<i32> {
+        if let Some((ratio_width, ratio_height)) = self.aspect_ratio {
+            let (width, ratio_width, ratio_height) =
+                (width as i64, ratio_width as i64, ratio_height as i64);
+            let height = (width * ratio_height + ratio_width - 1) / ratio_width;
+            let Ok(height) = i32::try_from(height) else {
+                return vec![];
+            };
+            if self.power_of_two && (height <= 0 || height & (height - 1) != 0) {
+                return vec![];
+            }
+            return vec![height];
+        }
+        if self.power_of_two {
+            return powers_of_two(min_height, max_height);
+        }
+        (min_height..=max_height).collect()
+    }
+    /// Searches for the smallest-area bin that holds every box.
+    ///
+    /// Candidate widths are tried from narrowest to widest. For each width the shortest
+    /// height that `MaxRects` can fill is found by bisection, and widths that cannot beat the
+    /// best area found so far are skipped. Ties in area go to the squarer bin.
+    ///
+    /// Without a fixed width or power-of-two sides, every width is tried only while there
+    /// are at most 256 of them. Over wider ranges the widths grow in geometric steps, so the
+    /// bin found may be a few percent larger than the best width would give.
+    ///
+    /// With both an aspect ratio and power-of-two sides, only widths whose scaled height is
+    /// also a power of two are considered.
+    ///
+    /// The search relies on the greedy packer, so the result is the smallest bin `MaxRects`
+    /// manages to fill, which is not necessarily the smallest bin that could hold the boxes.
+    ///
+    /// # Returns
+    /// The bin, with `bucketid` 0 and its origin at `(0, 0)`, together with the placed boxes,
+    /// or `None` if no bin within the constraints holds every box.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{enclosing::EnclosingBin, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(10, 10); 4];
+    /// let (bin, placed) = EnclosingBin::new(boxes).solve().unwrap();
+    /// assert_eq!((bin.width, bin.height), (20, 20));
+    /// assert_eq!(placed.len(), 4);
+    /// ```
+    pub fn solve(&self) -> Option<(Bucket, Vec<PackingBox>)> {
+        if self.boxes.is_empty() {
+            return None;
+        }
+        let allow_rotation = self.packer.allow_rotation;
+        let sides: Vec<(i32, i32)> = self
+            .boxes
+            .iter()
+            .map(|b| {
+                let orientations = b.orientations(allow_rotation, None);
+                let narrowest = orientations.iter().map(|o| o.0).min().unwrap();
+                let shortest = orientations.iter().map(|o| o.1).min().unwrap();
+                (narrowest, shortest)
+            })
+            .collect();
+        let area: i64 = self
+            .boxes
+            .iter()
+            .map(|b| b.width as i64 * b.height as i64)
+            .sum();
+        let longest = |b: &PackingBox| i32::max(b.width, b.height) as i64;
+        // Laid out in a single row or column the boxes never need more than this.
+        let needed: i64 = self.boxes.iter().map(longest).sum();
+        let needed = match self.power_of_two {
+            true => (needed as u64).next_power_of_two().min(1 << 30) as i32,
+            false => needed.min(i32::MAX as i64) as i32,
+        };
+        let limit = self.max_side.unwrap_or(needed);
+        let reach = limit.min(needed);
+        // Heights set by the aspect ratio can go past `reach`.
+        let tallest = match self.aspect_ratio {
+            Some(_) => limit,
+            None => reach,
+        };
+        let min_width = sides.iter().map(|s| s.0).max().unwrap();
+        let min_height = sides.iter().map(|s| s.1).max().unwrap();
+
+        let widths: Vec<i32> = match self.fixed_width {
+            Some(width) => vec![width],
+            None if self.power_of_two => powers_of_two(min_width, reach),
+            None => spread(min_width, reach, MAX_WIDTHS),
+        };
+
+        let mut best: Option<(Bucket, Vec<PackingBox>)> = None;
+        for width in widths {
+            if width < min_width || width > limit {
+                continue;
+            }
+            let best_area = best.as_ref().map_or(i64::MAX, |(bin, _)| bin.area());
+            let lower = i64::max(min_height as i64, (area + width as i64 - 1) / width as i64);
+            let lower = lower.min(tallest as i64 + 1) as i32;
+            if width as i64 * lower as i64 > best_area {
+                if self.fixed_width.is_none() && width as i64 * min_height as i64 > best_area {
+                    break;
+                }
+                continue;
+            }
+
+            let heights: Vec<i32> = self
+                .heights(width, lower, tallest)
+                .into_iter()
+                .filter(|&h| h >= lower && h <= tallest && width as i64 * h as i64 <= best_area)
+                .collect();
+            let Some(&tallest) = heights.last() else {
+                continue;
+            };
+            let Some(mut found) = self.pack(width, tallest).map(|p| (tallest, p)) else {
+                continue;
+            };
+
+            // Bisect for the shortest height that still fits.
+            let (mut lo, mut hi) = (0, heights.len() - 1);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                match self.pack(width, heights[mid]) {
+                    Some(placed) => {
+                        found = (heights[mid], placed);
+                        hi = mid;
+                    }
+                    None => lo = mid + 1,
+                }
+            }
+
+            let (height, placed) = found;
+            let candidate = Bucket::new(width, height, 0, 0, 0);
+            let better = match &best {
+                None => true,
+                Some((bin, _)) => {
+                    candidate.area() < bin.area()
+                        || (candidate.area() == bin.area()
+                            && (width - height).abs() < (bin.width - bin.height).abs())
+                }
+            };
+            if better {
+                best = Some((candidate, placed));
+            }
+        }
+        best
+    }
+}
+
+/// Returns the integers from `min` to `max`, inclusive, if there are at most `count` of them.
+/// Otherwise returns about `count` values from `min` to `max` growing in geometric steps.
+fn spread(min: i32, max: i32, count: usize) -> Vec<i32> {
+    if min > max {
+        return vec![];
+    }
+    if (max as i64 - min as i64) < count as i64 {
+        return (min..=max).collect();
+    }
+    let ratio = (max as f64 / min.max(1) as f64).powf(1.0 / count as f64);
+    let mut values = vec![];
+    let mut value = min;
+    while value < max {
+        values.push(value);
+        let next = (value as f64 * ratio).ceil() as i64;
+        value = next.clamp(value as i64 + 1, max as i64) as i32;
+    }
+    values.push(max);
+    values
+}
+
+/// Returns the powers of two between `min` and `max`, inclusive.
+fn powers_of_two(min: i32, max: i32) -> Vec<i32> {
+    let mut sizes = vec![];
+    let mut size: i32 = 1;
+    while size <= max {
+        if size >= min {
+            sizes.push(size);
+        }
+        match size.checked_mul(2) {
+            Some(doubled) => size = doubled,
+            None => break,
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_inside(bin: &Bucket, placed: &[PackingBox]) {
+        for box_item in placed {
+            let (x1, x2, y1, y2) = box_item.get_coords();
+            assert!(x1 >= 0 && y1 >= 0 && x2 <= bin.width && y2 <= bin.height);
+        }
+    }
+
+    #[test]
+    fn test_solve_tight_strip() {
+        let boxes = vec![PackingBox::new(10, 4), PackingBox::new(10, 6)];
+        let (bin, placed) = EnclosingBin::new(boxes).solve().unwrap();
+
+        assert_eq!(bin.area(), 100);
+        assert_eq!((bin.width, bin.height), (10, 10));
+        assert_inside(&bin, &placed);
+    }
+
+    #[test]
+    fn test_solve_fixed_width() {
+        let boxes = vec![PackingBox::new(10, 10); 4];
+        let (bin, placed) = EnclosingBin::new(boxes)
+            .with_fixed_width(10)
+            .solve()
+            .unwrap();
+
+        assert_eq!((bin.width, bin.height), (10, 40));
+        assert_eq!(placed.len(), 4);
+    }
+
+    #[test]
+    fn test_solve_power_of_two() {
+        let boxes = vec![PackingBox::new(10, 10); 4];
+        let (bin, placed) = EnclosingBin::new(boxes)
+            .with_power_of_two(true)
+            .solve()
+            .unwrap();
+
+        assert_eq!((bin.width, bin.height), (32, 32));
+        assert_inside(&bin, &placed);
+    }
+
+    #[test]
+    fn test_solve_aspect_ratio() {
+        let boxes = vec![PackingBox::new(10, 10); 3];
+        let (bin, placed) = EnclosingBin::new(boxes)
+            .with_aspect_ratio(2, 1)
+            .solve()
+            .unwrap();
+
+        assert_eq!((bin.width, bin.height), (30, 15));
+        assert_inside(&bin, &placed);
+    }
+
+    #[test]
+    fn test_solve_max_side() {
+        let boxes = vec![PackingBox::new(10, 10); 4];
+        assert!(EnclosingBin::new(boxes.clone())
+            .with_max_side(15)
+            .solve()
+            .is_none());
+
+        let (bin, _) = EnclosingBin::new(boxes)
+            .with_fixed_width(40)
+            .with_max_side(40)
+            .solve()
+            .unwrap();
+        assert_eq!((bin.width, bin.height), (40, 10));
+    }
+
+    #[test]
+    fn test_solve_large_max_side() {
+        let boxes = vec![PackingBox::new(10, 10)];
+        let (bin, _) = EnclosingBin::new(boxes.clone())
+            .with_power_of_two(true)
+            .with_max_side(i32::MAX)
+            .solve()
+            .unwrap();
+        assert_eq!((bin.width, bin.height), (16, 16));
+
+        let (bin, _) = EnclosingBin::new(boxes)
+            .with_max_side(i32::MAX)
+            .solve()
+            .unwrap();
+        assert_eq!((bin.width, bin.height), (10, 10));
+    }
+
+    #[test]
+    fn test_solve_aspect_ratio_power_of_two() {
+        let boxes = vec![PackingBox::new(10, 10); 3];
+        let (bin, placed) = EnclosingBin::new(boxes.clone())
+            .with_aspect_ratio(2, 1)
+            .with_power_of_two(true)
+            .with_max_side(64)
+            .solve()
+            .unwrap();
+        assert_eq!((bin.width, bin.height), (32, 16));
+        assert_inside(&bin, &placed);
+
+        // A third of a power of two is never a power of two.
+        let solved = EnclosingBin::new(boxes)
+            .with_aspect_ratio(3, 1)
+            .with_power_of_two(true)
+            .with_max_side(64)
+            .solve();
+        assert!(solved.is_none());
+    }
+
+    #[test]
+    fn test_solve_many_boxes() {
+        let boxes = vec![PackingBox::new(64, 64); 200];
+        let (bin, placed) = EnclosingBin::new(boxes).solve().unwrap();
+
+        assert_eq!(placed.len(), 200);
+        assert!(bin.area() as f64 <= 200.0 * 64.0 * 64.0 * 1.1);
+        assert_inside(&bin, &placed);
+    }
+
+    #[test]
+    fn test_spread() {
+        assert_eq!(spread(3, 6, 10), vec![3, 4, 5, 6]);
+        assert!(spread(7, 6, 10).is_empty());
+
+        let values = spread(64, 12_800, 256);
+        assert!(values.len() <= 300);
+        assert_eq!((values[0], *values.last().unwrap()), (64, 12_800));
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_powers_of_two() {
+        assert_eq!(powers_of_two(5, 40), vec![8, 16, 32]);
+        assert!(powers_of_two(50, 40).is_empty());
+        assert_eq!(powers_of_two(1 << 29, i32::MAX), vec![1 << 29, 1 << 30]);
+    }
+}
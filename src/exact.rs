@@ -0,0 +1,627 @@
+//! The `exact` module decides by branch and bound whether a small set of boxes fits into a
+//! set of bins, returning a layout or a proof that none exists. It is meant for instances of
+//! up to around 20 boxes, to check whether a heuristic answer is truly optimal.
+
+use crate::bucket::Bucket;
+use crate::packing_box::{Orientation, PackingBox};
+use crate::sort::{SortKey, SortStrategy};
+
+/// The answer of the exact solver.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// Every box fits. Holds the placed boxes, in input order.
+    Feasible(Vec<PackingBox>),
+    /// The search space was exhausted without finding a layout, which proves none exists.
+    Infeasible,
+    /// The node limit was reached before the question was settled.
+    Unknown,
+}
+
+/// An `Exact` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `allow_rotation`: Whether boxes may be turned by 90 degrees to fit. Each box's own
+///   `orientation` can further restrict or force rotation.
+/// - `node_limit`: The number of search nodes after which the solver gives up.
+#[derive(Debug, Clone)]
+pub struct Exact {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub allow_rotation: bool,
+    pub node_limit: usize,
+}
+
+/// A box placed during the search, relative to the top-left corner of its bin.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    bin: usize,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    rotate: bool,
+}
+
+impl Slot {
+    /// Returns the part of `obstacle` inside `bin` as a slot, or `None` if they do not meet.
+    fn obstacle(bin_index: usize, bin: &Bucket, obstacle: &Bucket) -> Option<Slot> {
+        let x1 = (obstacle.originx - bin.originx).max(0);
+        let x2 = (obstacle.originx + obstacle.width - bin.originx).min(bin.width);
+        let bottom = bin.originy + bin.height;
+        let y1 = (bottom - obstacle.originy - obstacle.height).max(0);
+        let y2 = (bottom - obstacle.originy).min(bin.height);
+        (x1 < x2 && y1 < y2).then_some(Slot {
+            bin: bin_index,
+            x: x1,
+            y: y1,
+            width: x2 - x1,
+            height: y2 - y1,
+            rotate: false,
+        })
+    }
+    fn overlaps(&self, other: &Slot) -> bool {
+        self.bin == other.bin
+            && self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// The state of one branch-and-bound run.
+struct Search<'a> {
+    /// The boxes in search order, each with its index in the input.
+    items: Vec<(usize, PackingBox)>,
+    /// Whether each item is identical to the one before it.
+    same_as_previous: Vec<bool>,
+    bins: &'a [Bucket],
+    /// The x and y offsets worth trying in each bin.
+    positions: Vec<(Vec<i32>, Vec<i32>)>,
+    allow_rotation: bool,
+    /// Whether some box is limited to certain bins, so bins with different ids differ.
+    restricted: bool,
+    /// The parts of the bins taken up by obstacles.
+    blocked: Vec<Slot>,
+    slots: Vec<Slot>,
+    free_area: Vec<i64>,
+    nodes: usize,
+    node_limit: usize,
+}
+
+impl Exact {
+    /// Constructs a new `Exact` instance without rotation and with a limit of one million
+    /// search nodes.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Exact` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        Self {
+            boxes,
+            bins,
+            allow_rotation: false,
+            node_limit: 1_000_000,
+        }
+    }
+    /// Allows or forbids turning boxes by 90 degrees.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+    /// Sets the number of search nodes after which the solver gives up.
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = node_limit;
+        self
+    }
+    /// Decides whether every box fits into the bins.
+    ///
+    /// Boxes are placed largest first. Each box is tried at every normal position of every
+    /// bin: offsets from the bin's edge that are sums of box sides, which are enough to find
+    /// a layout whenever one exists. Branches are cut when the remaining boxes cannot fit in
+    /// the remaining area, identical boxes in a bin are placed in a fixed order, and of
+    /// several identical empty bins only the first is tried.
+    ///
+    /// As in `MaxRects`, boxes in the returned layout sit against the bottom of their bin.
+    /// Obstacles in the bins are kept clear, boxes only go into bins their `allowed_bins`
+    /// permit, and boxes sharing a `group` all go into the bin with the same `bucketid`.
+    /// Padding, alignment and the other spacing options of `MaxRects` are not applied.
+    ///
+    /// # Returns
+    /// `Feasible` with the placed boxes, `Infeasible` if no layout exists, or `Unknown` if
+    /// the node limit was reached first.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, exact::{Exact, Outcome}, packing_box::PackingBox};
+    ///
+    /// let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+    /// let fits = Exact::new(vec![PackingBox::new(6, 4), PackingBox::new(4, 10)], bins.clone());
+    /// assert!(matches!(fits.solve(), Outcome::Feasible(_)));
+    ///
+    /// let too_wide = Exact::new(vec![PackingBox::new(6, 6), PackingBox::new(6, 6)], bins);
+    /// assert!(matches!(too_wide.solve(), Outcome::Infeasible));
+    /// ```
+    pub fn solve(&self) -> Outcome {
+        let sort = SortStrategy::descending(SortKey::Area);
+        let mut items: Vec<(usize, PackingBox)> = self.boxes.iter().cloned().enumerate().collect();
+        items.sort_by(|a, b| {
+            sort.compare(&a.1, &b.1)
+                .then((a.1.width, a.1.height).cmp(&(b.1.width, b.1.height)))
+        });
+        let same_as_previous = (0..items.len())
+            .map(|k| k > 0 && same_shape(&items[k - 1].1, &items[k].1))
+            .collect();
+        let blocked: Vec<Slot> = self
+            .bins
+            .iter()
+            .enumerate()
+            .flat_map(|(i, bin)| {
+                bin.obstacles
+                    .iter()
+                    .filter_map(move |obstacle| Slot::obstacle(i, bin, obstacle))
+            })
+            .collect();
+        let positions = (0..self.bins.len())
+            .map(|i| {
+                // Boxes can also be pushed up against the far side of an obstacle.
+                let mut xs = vec![0];
+                let mut ys = vec![0];
+                for slot in blocked.iter().filter(|slot| slot.bin == i) {
+                    xs.push(slot.x + slot.width);
+                    ys.push(slot.y + slot.height);
+                }
+                let xs = self.offsets(self.bins[i].width, true, &xs);
+                let ys = self.offsets(self.bins[i].height, false, &ys);
+                (xs, ys)
+            })
+            .collect();
+
+        let mut search = Search {
+            items,
+            same_as_previous,
+            bins: &self.bins,
+            positions,
+            allow_rotation: self.allow_rotation,
+            restricted: self.boxes.iter().any(|b| b.allowed_bins.is_some()),
+            blocked,
+            slots: vec![],
+            // Obstacles may overlap, so their area is not taken off; the bound stays valid.
+            free_area: self.bins.iter().map(|b| b.area()).collect(),
+            nodes: 0,
+            node_limit: self.node_limit,
+        };
+        let remaining_area = self
+            .boxes
+            .iter()
+            .map(|b| b.width as i64 * b.height as i64)
+            .sum();
+
+        match search.place(0, remaining_area) {
+            Some(true) => Outcome::Feasible(search.layout()),
+            Some(false) => Outcome::Infeasible,
+            None => Outcome::Unknown,
+        }
+    }
+    /// Finds the fewest leading bins that hold every box.
+    ///
+    /// Bins are added in the order given, so `bins[..k]` is tried for growing `k`.
+    ///
+    /// # Returns
+    /// `Feasible` with the layout in the fewest bins, `Infeasible` if not even all bins hold
+    /// the boxes, or `Unknown` if a smaller number of bins could not be settled.
+    pub fn minimize_bins(&self) -> Outcome {
+        for count in 1..=self.bins.len() {
+            let attempt = Exact {
+                bins: self.bins[..count].to_vec(),
+                ..self.clone()
+            };
+            match attempt.solve() {
+                Outcome::Infeasible => continue,
+                outcome => return outcome,
+            }
+        }
+        Outcome::Infeasible
+    }
+    /// Returns the offsets along one side of a bin, of length `limit`, that are one of
+    /// `starts` plus a sum of box sides running the same way.
+    ///
+    /// Boxes that may be turned contribute both of their sides.
+    fn offsets(&self, limit: i32, horizontal: bool, starts: &[i32]) -> Vec<i32> {
+        let limit = limit.max(0) as usize;
+        let mut reachable = vec![false; limit + 1];
+        for &start in starts.iter().filter(|&&o| o >= 0 && o as usize <= limit) {
+            reachable[start as usize] = true;
+        }
+
+        for box_item in &self.boxes {
+            let (along, across) = match horizontal {
+                true => (box_item.width, box_item.height),
+                false => (box_item.height, box_item.width),
+            };
+            let turns = match box_item.orientation {
+                Orientation::Fixed => false,
+                Orientation::Grain(_) => true,
+                Orientation::Rotatable => self.allow_rotation,
+            };
+            let mut lengths = vec![along as usize];
+            if turns {
+                lengths.push(across as usize);
+            }
+
+            let before = reachable.clone();
+            for length in lengths {
+                for offset in (0..=limit).filter(|&o| before[o] && o + length <= limit) {
+                    reachable[offset + length] = true;
+                }
+            }
+        }
+
+        (0..=limit as i32)
+            .filter(|&o| reachable[o as usize])
+            .collect()
+    }
+}
+
+impl Search<'_> {
+    /// Places the items from `k` onwards.
+    ///
+    /// # Returns
+    /// Whether a layout was found, or `None` if the node limit was reached.
+    fn place(&mut self, k: usize, remaining_area: i64) -> Option<bool> {
+        if k == self.items.len() {
+            return Some(true);
+        }
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return None;
+        }
+        if remaining_area > self.free_area.iter().sum() {
+            return Some(false);
+        }
+
+        let box_item = self.items[k].1.clone();
+        let area = box_item.width as i64 * box_item.height as i64;
+        // A group goes wherever its first placed box went.
+        let home = box_item.group.as_ref().and_then(|group| {
+            let first = self.items[..k]
+                .iter()
+                .position(|(_, b)| b.group.as_ref() == Some(group))?;
+            Some(self.bins[self.slots[first].bin].bucketid)
+        });
+        let previous = match self.same_as_previous[k] {
+            true => self.slots.last().copied(),
+            false => None,
+        };
+
+        for bin in 0..self.bins.len() {
+            let container = &self.bins[bin];
+            if self.free_area[bin] < area
+                || !box_item.allows(container.bucketid)
+                || home.is_some_and(|id| id != container.bucketid)
+                || self.skip_empty_bin(bin)
+            {
+                continue;
+            }
+            for (width, height, rotate) in
+                box_item.orientations(self.allow_rotation, container.grain)
+            {
+                let (xs, ys) = &self.positions[bin];
+                let ys: Vec<i32> = ys
+                    .iter()
+                    .copied()
+                    .filter(|&y| y + height <= container.height)
+                    .collect();
+                let xs: Vec<i32> = xs
+                    .iter()
+                    .copied()
+                    .filter(|&x| x + width <= container.width)
+                    .collect();
+
+                for &y in &ys {
+                    for &x in &xs {
+                        // Identical boxes in the same bin are placed in reading order.
+                        if let Some(prev) = previous {
+                            if prev.bin == bin && (y, x) <= (prev.y, prev.x) {
+                                continue;
+                            }
+                        }
+                        let slot = Slot {
+                            bin,
+                            x,
+                            y,
+                            width,
+                            height,
+                            rotate,
+                        };
+                        if self
+                            .slots
+                            .iter()
+                            .chain(&self.blocked)
+                            .any(|other| other.overlaps(&slot))
+                        {
+                            continue;
+                        }
+
+                        self.slots.push(slot);
+                        self.free_area[bin] -= area;
+                        let found = self.place(k + 1, remaining_area - area);
+                        if found != Some(false) {
+                            return found;
+                        }
+                        self.free_area[bin] += area;
+                        self.slots.pop();
+                    }
+                }
+            }
+        }
+        Some(false)
+    }
+    /// Whether `bin` is empty and an earlier empty bin of the same shape exists, in which case
+    /// trying `bin` would only repeat that branch.
+    fn skip_empty_bin(&self, bin: usize) -> bool {
+        let empty = |b: usize| {
+            self.bins[b].obstacles.is_empty() && !self.slots.iter().any(|slot| slot.bin == b)
+        };
+        let shape = |b: &Bucket| {
+            let id = self.restricted.then_some(b.bucketid);
+            (b.width, b.height, b.grain, id)
+        };
+        empty(bin)
+            && (0..bin).any(|earlier| {
+                empty(earlier) && shape(&self.bins[earlier]) == shape(&self.bins[bin])
+            })
+    }
+    /// Returns the placed boxes, in input order, in the coordinates of their bins.
+    fn layout(&self) -> Vec<PackingBox> {
+        let mut placed: Vec<(usize, PackingBox)> = self
+            .items
+            .iter()
+            .zip(&self.slots)
+            .map(|((idx, box_item), slot)| {
+                let bin = &self.bins[slot.bin];
+                let mut box_item = box_item.clone();
+                if slot.rotate {
+                    box_item.rotate();
+                }
+                box_item.place(
+                    bin.originx + slot.x,
+                    bin.originy + bin.height - slot.y - slot.height,
+                    bin.bucketid,
+                );
+                (*idx, box_item)
+            })
+            .collect();
+        placed.sort_by_key(|(idx, _)| *idx);
+        placed.into_iter().map(|(_, box_item)| box_item).collect()
+    }
+}
+
+/// Whether two boxes are interchangeable in a layout.
+fn same_shape(a: &PackingBox, b: &PackingBox) -> bool {
+    (a.width, a.height, a.rotated, a.orientation) == (b.width, b.height, b.rotated, b.orientation)
+        && a.allowed_bins == b.allowed_bins
+        && a.group == b.group
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::max_rects::{MaxRects, SearchMode};
+    use crate::sort::SortStrategy;
+
+    fn assert_valid(placed: &[PackingBox], bins: &[Bucket]) {
+        for (i, a) in placed.iter().enumerate() {
+            let bin = bins
+                .iter()
+                .find(|b| Some(b.bucketid) == a.bucketid)
+                .unwrap();
+            let (x1, x2, y1, y2) = a.get_coords();
+            let (bx1, bx2, by1, by2) = bin.get_coords();
+            assert!(x1 >= bx1 && x2 <= bx2 && y1 >= by1 && y2 <= by2);
+            for b in &placed[i + 1..] {
+                if a.bucketid == b.bucketid {
+                    let (ox1, ox2, oy1, oy2) = b.get_coords();
+                    assert!(x2 <= ox1 || ox2 <= x1 || y2 <= oy1 || oy2 <= y1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_solves_what_greedy_misses() {
+        let boxes = vec![
+            PackingBox::new(8, 2),
+            PackingBox::new(4, 3),
+            PackingBox::new(4, 5),
+            PackingBox::new(1, 1),
+            PackingBox::new(3, 7),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+
+        let (_, unplaced, _) = MaxRects::new(boxes.clone(), bins.clone())
+            .with_search(SearchMode::InOrder)
+            .with_sort(SortStrategy::default())
+            .place();
+        assert_eq!(unplaced.len(), 1);
+
+        match Exact::new(boxes, bins.clone()).solve() {
+            Outcome::Feasible(placed) => {
+                assert_eq!(placed.len(), 5);
+                assert_eq!((placed[0].width, placed[0].height), (8, 2));
+                assert_valid(&placed, &bins);
+            }
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_proves_infeasibility() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        let boxes = vec![PackingBox::new(4, 4); 5];
+        assert!(matches!(
+            Exact::new(boxes, bins.clone()).solve(),
+            Outcome::Infeasible
+        ));
+
+        let boxes = vec![PackingBox::new(2, 2); 25];
+        assert!(matches!(
+            Exact::new(boxes, bins).solve(),
+            Outcome::Feasible(_)
+        ));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let boxes = vec![
+            PackingBox::new(10, 4),
+            PackingBox::new(10, 4),
+            PackingBox::new(2, 10),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        assert!(matches!(
+            Exact::new(boxes.clone(), bins.clone()).solve(),
+            Outcome::Infeasible
+        ));
+
+        match Exact::new(boxes, bins.clone()).with_rotation(true).solve() {
+            Outcome::Feasible(placed) => {
+                assert!(placed[2].rotated);
+                assert_valid(&placed, &bins);
+            }
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_several_bins() {
+        let boxes = vec![PackingBox::new(6, 6); 3];
+        let bins = vec![
+            Bucket::new(10, 10, 0, 0, 0),
+            Bucket::new(10, 10, 20, 0, 1),
+            Bucket::new(10, 10, 40, 0, 2),
+        ];
+        match Exact::new(boxes.clone(), bins.clone()).solve() {
+            Outcome::Feasible(placed) => assert_valid(&placed, &bins),
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+        assert!(matches!(
+            Exact::new(boxes, bins[..2].to_vec()).solve(),
+            Outcome::Infeasible
+        ));
+    }
+
+    #[test]
+    fn test_minimize_bins() {
+        let boxes = vec![PackingBox::new(5, 5); 4];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)];
+        match Exact::new(boxes, bins).minimize_bins() {
+            Outcome::Feasible(placed) => {
+                assert!(placed.iter().all(|b| b.bucketid == Some(0)));
+            }
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+
+        let boxes = vec![PackingBox::new(11, 1)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        assert!(matches!(
+            Exact::new(boxes, bins).minimize_bins(),
+            Outcome::Infeasible
+        ));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let boxes = vec![PackingBox::new(4, 4); 5];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        assert!(matches!(
+            Exact::new(boxes, bins).with_node_limit(2).solve(),
+            Outcome::Unknown
+        ));
+    }
+
+    #[test]
+    fn test_offsets() {
+        let exact = Exact::new(vec![PackingBox::new(3, 5), PackingBox::new(4, 2)], vec![]);
+        assert_eq!(exact.offsets(10, true, &[0]), vec![0, 3, 4, 7]);
+        assert_eq!(exact.offsets(10, false, &[0]), vec![0, 2, 5, 7]);
+        assert_eq!(
+            exact.offsets(10, true, &[0, 2]),
+            vec![0, 2, 3, 4, 5, 6, 7, 9]
+        );
+
+        let exact = exact.with_rotation(true);
+        assert_eq!(exact.offsets(10, true, &[0]), vec![0, 2, 3, 4, 5, 7, 9]);
+    }
+
+    #[test]
+    fn test_obstacles() {
+        let boxes = vec![PackingBox::new(5, 10), PackingBox::new(3, 10)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0).with_obstacle(2, 10, 5, 0)];
+        match Exact::new(boxes.clone(), bins.clone()).solve() {
+            Outcome::Feasible(placed) => {
+                assert_valid(&placed, &bins);
+                assert!(placed.iter().all(|b| !b.overlap(&bins[0].obstacles[0])));
+            }
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0).with_obstacle(2, 10, 4, 0)];
+        assert!(matches!(
+            Exact::new(boxes, bins).solve(),
+            Outcome::Infeasible
+        ));
+
+        let boxes = vec![PackingBox::new(10, 4)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0).with_obstacle(10, 6, 0, 4)];
+        match Exact::new(boxes, bins).solve() {
+            Outcome::Feasible(placed) => assert_eq!(placed[0].originy, Some(0)),
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_allowed_bins() {
+        let boxes = vec![PackingBox::new(6, 6).with_allowed_bins([1])];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 20, 0, 1)];
+        match Exact::new(boxes, bins.clone()).solve() {
+            Outcome::Feasible(placed) => assert_eq!(placed[0].bucketid, Some(1)),
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+
+        let boxes = vec![PackingBox::new(6, 6).with_allowed_bins([0]); 2];
+        assert!(matches!(
+            Exact::new(boxes, bins).solve(),
+            Outcome::Infeasible
+        ));
+    }
+
+    #[test]
+    fn test_groups() {
+        let boxes = vec![
+            PackingBox::new(6, 6).with_group("a"),
+            PackingBox::new(6, 6).with_group("a"),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 20, 0, 1)];
+        assert!(matches!(
+            Exact::new(boxes, bins.clone()).solve(),
+            Outcome::Infeasible
+        ));
+
+        let boxes = vec![
+            PackingBox::new(6, 4).with_group("a"),
+            PackingBox::new(6, 6),
+            PackingBox::new(6, 4).with_group("a"),
+        ];
+        match Exact::new(boxes, bins.clone()).solve() {
+            Outcome::Feasible(placed) => {
+                assert_eq!(placed[0].bucketid, placed[2].bucketid);
+                assert_ne!(placed[0].bucketid, placed[1].bucketid);
+                assert_valid(&placed, &bins);
+            }
+            outcome => panic!("expected a layout, got {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! The `grouping` module keeps boxes that share a `group` together for every packer: each
+//! group ends up whole in a single bin or is left out altogether.
+
+use std::collections::HashMap;
+
+use crate::bucket::Bucket;
+use crate::packing_box::PackingBox;
+
+/// The placed boxes, the unplaced boxes and the free space left by one packing.
+pub(crate) type Packed = (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>);
+
+/// Returns the bin `box_item` has to go in to join its group: the bin of a member in
+/// `placed`, or else the bin the group is held to in `pins`.
+pub(crate) fn home(
+    box_item: &PackingBox,
+    placed: &[PackingBox],
+    pins: &HashMap<String, i32>,
+) -> Option<i32> {
+    let group = box_item.group.as_ref()?;
+    placed
+        .iter()
+        .find(|b| b.group.as_ref() == Some(group))
+        .and_then(|b| b.bucketid)
+        .or_else(|| pins.get(group).copied())
+}
+
+/// Packs `boxes` with `pack` so that every group ends up in a single bin or not at all.
+///
+/// `pack` starts from scratch each time it is called, placing the boxes it is given and
+/// sending each box to its `home` bin. When only part of a group fits there, the group is
+/// held to the next bin of `order` all its members may go in and the packing starts over; a
+/// group that fits whole in no bin is left unplaced.
+///
+/// # Returns
+/// The last packing, with the boxes of dropped groups added to the unplaced boxes.
+pub(crate) fn place_grouped(
+    boxes: &[PackingBox],
+    order: &[i32],
+    mut pack: impl FnMut(Vec<PackingBox>, &HashMap<String, i32>) -> Packed,
+) -> Packed {
+    let mut pins: HashMap<String, i32> = HashMap::new();
+    let mut tried: HashMap<String, Vec<i32>> = HashMap::new();
+    let mut dropped: Vec<String> = vec![];
+
+    loop {
+        let kept: Vec<PackingBox> = boxes
+            .iter()
+            .filter(|b| {
+                b.group
+                    .as_ref()
+                    .is_none_or(|group| !dropped.contains(group))
+            })
+            .cloned()
+            .collect();
+        let (placed, mut unplaced, free) = pack(kept, &pins);
+
+        let mut split: Vec<String> = vec![];
+        for group in unplaced.iter().filter_map(|b| b.group.as_ref()) {
+            if !split.contains(group) && placed.iter().any(|b| b.group.as_ref() == Some(group)) {
+                split.push(group.clone());
+            }
+        }
+        if split.is_empty() {
+            unplaced.extend(
+                boxes
+                    .iter()
+                    .filter(|b| {
+                        b.group
+                            .as_ref()
+                            .is_some_and(|group| dropped.contains(group))
+                    })
+                    .cloned(),
+            );
+            return (placed, unplaced, free);
+        }
+
+        for group in split {
+            let members: Vec<&PackingBox> = boxes
+                .iter()
+                .filter(|b| b.group.as_ref() == Some(&group))
+                .collect();
+            let tried = tried.entry(group.clone()).or_default();
+            tried.extend(home(members[0], &placed, &pins));
+            tried.extend(pins.get(&group));
+
+            let next = order
+                .iter()
+                .find(|id| !tried.contains(id) && members.iter().all(|b| b.allows(**id)));
+            match next {
+                Some(&id) => {
+                    pins.insert(group, id);
+                }
+                None => {
+                    pins.remove(&group);
+                    dropped.push(group);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the `bucketid`s of `bins` in order, each once.
+pub(crate) fn bin_order(bins: &[Bucket]) -> Vec<i32> {
+    let mut order: Vec<i32> = vec![];
+    for bin in bins {
+        if !order.contains(&bin.bucketid) {
+            order.push(bin.bucketid);
+        }
+    }
+    order
+}
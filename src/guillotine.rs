@@ -0,0 +1,491 @@
+//! The `guillotine` module provides an implementation of the Guillotine bin packing algorithm.
+//! Every placement splits its free rectangle with a single edge-to-edge cut, so the free
+//! rectangles never overlap and every layout can be cut on a panel saw.
+
+extern crate rayon;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use crate::bucket::{merge_adjacent, Bucket};
+use crate::grouping::{self, Packed};
+use crate::packing_box::PackingBox;
+
+/// The rule used to choose which free rectangle a box goes into. Lower leftovers win for the
+/// `Best` rules and higher leftovers win for the `Worst` rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FreeRectChoice {
+    /// Minimizes the unused area of the free rectangle.
+    #[default]
+    BestAreaFit,
+    /// Minimizes the shorter leftover side of the free rectangle.
+    BestShortSideFit,
+    /// Minimizes the longer leftover side of the free rectangle.
+    BestLongSideFit,
+    /// Maximizes the unused area of the free rectangle.
+    WorstAreaFit,
+    /// Maximizes the shorter leftover side of the free rectangle.
+    WorstShortSideFit,
+    /// Maximizes the longer leftover side of the free rectangle.
+    WorstLongSideFit,
+}
+
+impl FreeRectChoice {
+    /// Scores placing a `width` by `height` box into `free`. Lower scores are better.
+    fn score(&self, width: i32, height: i32, free: &Bucket) -> i64 {
+        let leftover_x = (free.width - width) as i64;
+        let leftover_y = (free.height - height) as i64;
+        let area = free.area() - width as i64 * height as i64;
+        let short_side = i64::min(leftover_x, leftover_y);
+        let long_side = i64::max(leftover_x, leftover_y);
+
+        match self {
+            FreeRectChoice::BestAreaFit => area,
+            FreeRectChoice::BestShortSideFit => short_side,
+            FreeRectChoice::BestLongSideFit => long_side,
+            FreeRectChoice::WorstAreaFit => -area,
+            FreeRectChoice::WorstShortSideFit => -short_side,
+            FreeRectChoice::WorstLongSideFit => -long_side,
+        }
+    }
+}
+
+/// The rule used to decide which way the leftover space of a free rectangle is cut after a
+/// box has been placed in it.
+///
+/// A horizontal cut runs along the top of the box across the whole free rectangle, leaving a
+/// full-width strip above and a strip beside the box. A vertical cut runs along the right of
+/// the box, leaving a full-height strip to the right and a strip above the box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitRule {
+    /// Cuts along the axis whose leftover is shorter.
+    #[default]
+    ShorterLeftoverAxis,
+    /// Cuts along the axis whose leftover is longer.
+    LongerLeftoverAxis,
+    /// Cuts so that the smaller of the two new rectangles is as small as possible.
+    MinimizeArea,
+    /// Cuts so that the larger of the two new rectangles is as large as possible.
+    MaximizeArea,
+    /// Cuts along the shorter side of the free rectangle.
+    ShorterAxis,
+    /// Cuts along the longer side of the free rectangle.
+    LongerAxis,
+}
+
+impl SplitRule {
+    /// Returns `true` when the leftover space should be cut horizontally.
+    fn split_horizontal(&self, width: i32, height: i32, free: &Bucket) -> bool {
+        let leftover_x = (free.width - width) as i64;
+        let leftover_y = (free.height - height) as i64;
+        let (width, height) = (width as i64, height as i64);
+
+        match self {
+            SplitRule::ShorterLeftoverAxis => leftover_x <= leftover_y,
+            SplitRule::LongerLeftoverAxis => leftover_x > leftover_y,
+            SplitRule::MinimizeArea => width * leftover_y > leftover_x * height,
+            SplitRule::MaximizeArea => width * leftover_y <= leftover_x * height,
+            SplitRule::ShorterAxis => free.width <= free.height,
+            SplitRule::LongerAxis => free.width > free.height,
+        }
+    }
+}
+
+/// Places a `width` by `height` box in the bottom-left corner of `free` and cuts the leftover
+/// space in two according to `rule`.
+///
+/// # Returns
+/// The rectangle the box occupies, followed by the space above it and the space to its right.
+/// Either leftover may be empty.
+pub(crate) fn split(
+    free: &Bucket,
+    width: i32,
+    height: i32,
+    rule: SplitRule,
+) -> (Bucket, Bucket, Bucket) {
+    let horizontal = rule.split_horizontal(width, height, free);
+    let originy = free.originy + free.height - height;
+
+    let used = Bucket::new(width, height, free.originx, originy, free.bucketid);
+    let above = Bucket::new(
+        if horizontal { free.width } else { width },
+        free.height - height,
+        free.originx,
+        free.originy,
+        free.bucketid,
+    );
+    let right = if horizontal {
+        Bucket::new(
+            free.width - width,
+            height,
+            free.originx + width,
+            originy,
+            free.bucketid,
+        )
+    } else {
+        Bucket::new(
+            free.width - width,
+            free.height,
+            free.originx + width,
+            free.originy,
+            free.bucketid,
+        )
+    };
+    (used, above, right)
+}
+
+/// A `Guillotine` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of non-overlapping `Bucket` objects representing the available free space.
+/// - `containers`: The bins as originally supplied.
+/// - `choice`: The rule used to choose a free rectangle for each box.
+/// - `split`: The rule used to cut the leftover space after each placement.
+/// - `allow_rotation`: Whether boxes may be turned by 90 degrees to fit.
+#[derive(Debug, Clone)]
+pub struct Guillotine {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub containers: Vec<Bucket>,
+    pub choice: FreeRectChoice,
+    pub split: SplitRule,
+    pub allow_rotation: bool,
+    /// The bin each group is held to while `place` keeps groups together.
+    pins: HashMap<String, i32>,
+}
+
+impl Guillotine {
+    /// Constructs a new `Guillotine` instance using `BestAreaFit` and `ShorterLeftoverAxis`.
+    /// Obstacles in the bins are cut out of the free space.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Guillotine` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        Self {
+            boxes,
+            containers: bins.clone(),
+            bins: bins
+                .iter()
+                .flat_map(|bin| bin.subtract(&bin.obstacles))
+                .collect(),
+            choice: FreeRectChoice::default(),
+            split: SplitRule::default(),
+            allow_rotation: false,
+            pins: HashMap::new(),
+        }
+    }
+    /// Sets the rule used to choose a free rectangle for each box.
+    pub fn with_choice(mut self, choice: FreeRectChoice) -> Self {
+        self.choice = choice;
+        self
+    }
+    /// Sets the rule used to cut the leftover space after each placement.
+    pub fn with_split(mut self, split: SplitRule) -> Self {
+        self.split = split;
+        self
+    }
+    /// Allows or forbids turning boxes by 90 degrees during placement.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+    /// Finds the best free rectangle and orientation for `box_item` among the bins it allows,
+    /// keeping to the bin `home` when it is set.
+    ///
+    /// # Returns
+    /// The score, the index of the free rectangle and whether the box has to be turned.
+    pub(crate) fn best_fit(
+        &self,
+        box_item: &PackingBox,
+        containers: &HashMap<i32, Bucket>,
+        home: Option<i32>,
+    ) -> Option<(i64, usize, bool)> {
+        self.bins
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| {
+                box_item.allows(rect.bucketid) && home.is_none_or(|id| rect.bucketid == id)
+            })
+            .flat_map(|(j, rect)| {
+                let grain = containers.get(&rect.bucketid).and_then(|bin| bin.grain);
+                box_item
+                    .orientations(self.allow_rotation, grain)
+                    .into_iter()
+                    .map(move |(width, height, rotate)| (j, rect, width, height, rotate))
+            })
+            .filter(|(_, rect, width, height, _)| *width <= rect.width && *height <= rect.height)
+            .map(|(j, rect, width, height, rotate)| {
+                (self.choice.score(width, height, rect), j, rotate)
+            })
+            .min_by(|a, b| a.0.cmp(&b.0))
+    }
+    /// Places `box_item` into the free rectangle at `idx`, replacing that rectangle with the
+    /// two pieces left over by the cut.
+    pub(crate) fn commit(
+        &mut self,
+        mut box_item: PackingBox,
+        idx: usize,
+        rotate: bool,
+    ) -> PackingBox {
+        if rotate {
+            box_item.rotate();
+        }
+        let free = self.bins.remove(idx);
+        let (used, above, right) = split(&free, box_item.width, box_item.height, self.split);
+        box_item.place(used.originx, used.originy, used.bucketid);
+
+        for leftover in [right, above] {
+            if leftover.area() > 0 {
+                self.bins.push(leftover);
+            }
+        }
+        box_item
+    }
+    /// Merges neighbouring free rectangles that together form a single rectangle.
+    ///
+    /// Guillotine cuts never rejoin the pieces they create, so free space fragments quickly;
+    /// merging lets larger boxes use it again.
+    ///
+    /// # Returns
+    /// The number of merges performed.
+    pub fn defragment(&mut self) -> usize {
+        merge_adjacent(&mut self.bins)
+    }
+    /// Attempts to place the boxes into the bins.
+    ///
+    /// Each step places the box and free rectangle pair with the best score and cuts the rest
+    /// of that free rectangle in two. The process continues until no more boxes can be placed.
+    /// Boxes sharing a `group` are placed in the same bin or not at all.
+    ///
+    /// # Returns
+    /// A tuple of three vectors:
+    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
+    /// - A vector of `Bucket` objects representing the free space left in the bins.
+    pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        if !self.boxes.iter().any(|b| b.group.is_some()) {
+            return self.fill();
+        }
+        let start = self.clone();
+        let order = grouping::bin_order(&self.containers);
+        let (placed, unplaced, free) =
+            grouping::place_grouped(&start.boxes, &order, |boxes, pins| {
+                *self = start.clone();
+                self.boxes = boxes;
+                self.pins = pins.clone();
+                self.fill()
+            });
+        self.pins.clear();
+        self.boxes = unplaced.clone();
+        (placed, unplaced, free)
+    }
+    /// Places the boxes, sending each box that belongs to a group to its group's bin.
+    fn fill(&mut self) -> Packed {
+        let mut placed = vec![];
+        let containers: HashMap<i32, Bucket> = self
+            .containers
+            .iter()
+            .map(|bin| (bin.bucketid, bin.clone()))
+            .collect();
+
+        loop {
+            let search_result = self
+                .boxes
+                .par_iter()
+                .enumerate()
+                .filter_map(|(i, box_item)| {
+                    let home = grouping::home(box_item, &placed, &self.pins);
+                    self.best_fit(box_item, &containers, home)
+                        .map(|(score, j, rotate)| (score, i, j, rotate))
+                })
+                .min_by(|a, b| a.0.cmp(&b.0));
+
+            match search_result {
+                Some((_, box_idx, idx, rotate)) => {
+                    let box_item = self.boxes.remove(box_idx);
+                    placed.push(self.commit(box_item, idx, rotate));
+                }
+                None => break,
+            }
+        }
+
+        (placed, self.boxes.clone(), self.bins.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_horizontal() {
+        let free = Bucket::new(10, 20, 0, 0, 1);
+        let (used, above, right) = split(&free, 4, 6, SplitRule::ShorterAxis);
+
+        assert_eq!(used.get_coords(), (0, 4, 14, 20));
+        assert_eq!(above.get_coords(), (0, 10, 0, 14));
+        assert_eq!(right.get_coords(), (4, 10, 14, 20));
+    }
+
+    #[test]
+    fn test_split_vertical() {
+        let free = Bucket::new(10, 20, 0, 0, 1);
+        let (used, above, right) = split(&free, 4, 6, SplitRule::LongerAxis);
+
+        assert_eq!(used.get_coords(), (0, 4, 14, 20));
+        assert_eq!(above.get_coords(), (0, 4, 0, 14));
+        assert_eq!(right.get_coords(), (4, 10, 0, 20));
+    }
+
+    #[test]
+    fn test_place_fills_bin() {
+        let boxes = vec![
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut guillotine = Guillotine::new(boxes, bins);
+        let (placed, remaining, free) = guillotine.place();
+
+        assert_eq!(placed.len(), 4);
+        assert!(remaining.is_empty());
+        assert!(free.is_empty());
+    }
+
+    #[test]
+    fn test_free_rects_do_not_overlap() {
+        for split in [
+            SplitRule::ShorterLeftoverAxis,
+            SplitRule::LongerLeftoverAxis,
+            SplitRule::MinimizeArea,
+            SplitRule::MaximizeArea,
+            SplitRule::ShorterAxis,
+            SplitRule::LongerAxis,
+        ] {
+            let boxes = vec![
+                PackingBox::new(3, 7),
+                PackingBox::new(6, 2),
+                PackingBox::new(4, 4),
+                PackingBox::new(2, 9),
+            ];
+            let bins = vec![Bucket::new(12, 12, 0, 0, 1)];
+            let mut guillotine = Guillotine::new(boxes, bins)
+                .with_split(split)
+                .with_choice(FreeRectChoice::BestShortSideFit);
+            let (placed, _, free) = guillotine.place();
+
+            let placed_area: i64 = placed
+                .iter()
+                .map(|b| b.width as i64 * b.height as i64)
+                .sum();
+            let free_area: i64 = free.iter().map(|b| b.area()).sum();
+            assert_eq!(placed_area + free_area, 144, "{:?}", split);
+            for (i, a) in free.iter().enumerate() {
+                assert!(free.iter().skip(i + 1).all(|b| !a.overlap(b)));
+                assert!(placed.iter().all(|b| !b.overlap(a)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_place_rotated() {
+        let boxes = vec![PackingBox::new(20, 10)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+        let mut guillotine = Guillotine::new(boxes, bins).with_rotation(true);
+        let (placed, remaining, _) = guillotine.place();
+
+        assert!(remaining.is_empty());
+        assert!(placed[0].rotated);
+    }
+
+    #[test]
+    fn test_defragment() {
+        let boxes = vec![PackingBox::new(4, 4)];
+        let bins = vec![Bucket::new(10, 4, 0, 0, 1)];
+        let mut guillotine = Guillotine::new(boxes, bins);
+        guillotine.place();
+
+        // Returning the used space leaves two free pieces that line up.
+        guillotine.bins.push(Bucket::new(4, 4, 0, 0, 1));
+        assert_eq!(guillotine.defragment(), 1);
+        assert_eq!(guillotine.bins[0].get_coords(), (0, 10, 0, 4));
+    }
+
+    #[test]
+    fn test_obstacles() {
+        let sheet = Bucket::new(10, 10, 0, 0, 1).with_obstacle(2, 2, 4, 4);
+        let obstacle = sheet.obstacles[0].clone();
+
+        let mut guillotine = Guillotine::new(vec![PackingBox::new(5, 5)], vec![sheet.clone()]);
+        assert!(guillotine.place().0.is_empty());
+
+        let mut guillotine = Guillotine::new(vec![PackingBox::new(4, 4); 4], vec![sheet]);
+        let (placed, remaining, free) = guillotine.place();
+        assert_eq!(placed.len(), 4);
+        assert!(remaining.is_empty());
+        assert!(placed.iter().all(|b| !b.overlap(&obstacle)));
+        assert!(free.iter().all(|f| !f.overlap(&obstacle)));
+    }
+
+    #[test]
+    fn test_allowed_bins() {
+        let boxes = vec![
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5).with_allowed_bins([2]),
+        ];
+        let bins = vec![Bucket::new(5, 5, 0, 0, 0), Bucket::new(5, 5, 0, 0, 1)];
+        let mut guillotine = Guillotine::new(boxes, bins);
+        let (placed, remaining, _) = guillotine.place();
+
+        assert_eq!((placed.len(), remaining.len()), (2, 2));
+        assert!(placed.iter().all(|b| b.allows(b.bucketid.unwrap())));
+        assert!(remaining.iter().any(|b| b.allows(2) && !b.allows(1)));
+    }
+
+    #[test]
+    fn test_groups_share_a_bin() {
+        let mut boxes = vec![PackingBox::new(5, 5).with_group("a"); 3];
+        boxes.extend(vec![PackingBox::new(5, 5).with_group("b"); 2]);
+        boxes.push(PackingBox::new(5, 5));
+        boxes.push(PackingBox::new(5, 5).with_group("c"));
+        boxes.push(PackingBox::new(6, 6).with_group("c"));
+        let bins = vec![
+            Bucket::new(10, 5, 0, 0, 0),
+            Bucket::new(10, 10, 0, 0, 1),
+            Bucket::new(5, 5, 0, 0, 2),
+        ];
+        let mut guillotine = Guillotine::new(boxes, bins);
+        let (placed, remaining, _) = guillotine.place();
+
+        assert_eq!(placed.len(), 6);
+        // Group "c" fits whole in no bin, so neither of its boxes is placed.
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().all(|b| b.group.as_deref() == Some("c")));
+        for group in ["a", "b"] {
+            let mut ids: Vec<_> = placed
+                .iter()
+                .filter(|b| b.group.as_deref() == Some(group))
+                .map(|b| b.bucketid)
+                .collect();
+            ids.dedup();
+            assert_eq!(ids.len(), 1, "{}", group);
+        }
+        assert!(guillotine.pins.is_empty());
+    }
+
+    #[test]
+    fn test_worst_fit_prefers_larger_space() {
+        let boxes = vec![PackingBox::new(2, 2)];
+        let bins = vec![Bucket::new(3, 3, 0, 0, 1), Bucket::new(10, 10, 0, 0, 2)];
+        let mut guillotine = Guillotine::new(boxes, bins).with_choice(FreeRectChoice::WorstAreaFit);
+        let (placed, _, _) = guillotine.place();
+
+        assert_eq!(placed[0].bucketid, Some(2));
+    }
+}
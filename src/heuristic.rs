@@ -0,0 +1,203 @@
+//! Placement heuristics used by `MaxRects` to score candidate positions.
+//!
+//! Each heuristic turns a candidate position inside a free rectangle into a score, and the
+//! candidate with the lowest score wins. The built-in rules follow Jukka Jylänki's
+//! "A Thousand Ways to Pack the Bin"; custom rules can be supplied by implementing
+//! [`PlacementHeuristic`].
+
+use crate::bucket::Bucket;
+use crate::packing_box::PackingBox;
+
+/// Everything a heuristic may look at besides the candidate itself.
+///
+/// - `placed`: The boxes that have already been placed, in every bin.
+/// - `container`: The bin the free rectangle belongs to, as originally supplied.
+pub struct PlacementContext<'a> {
+    pub placed: &'a [PackingBox],
+    pub container: &'a Bucket,
+}
+
+/// Scores a candidate placement. Lower scores are better.
+///
+/// `candidate` is the rectangle the box would occupy and `free` is the free rectangle it is
+/// being placed into. The score is a `(primary, secondary)` pair compared lexicographically,
+/// so the secondary value only breaks ties.
+///
+/// # Examples
+///
+/// A heuristic preferring the free rectangle closest to the left edge:
+/// ```
+/// use max_rects::bucket::Bucket;
+/// use max_rects::heuristic::{PlacementContext, PlacementHeuristic};
+///
+/// struct LeftMost;
+///
+/// impl PlacementHeuristic for LeftMost {
+///     fn score(&self, candidate: &Bucket, _free: &Bucket, _context: &PlacementContext) -> (i64, i64) {
+///         (candidate.originx as i64, candidate.originy as i64)
+///     }
+/// }
+/// ```
+pub trait PlacementHeuristic: Send + Sync {
+    fn score(&self, candidate: &Bucket, free: &Bucket, context: &PlacementContext) -> (i64, i64);
+}
+
+/// The placement rules described by Jylänki for the MaxRects algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Heuristic {
+    /// Minimizes the shorter leftover side of the free rectangle, then the longer one.
+    #[default]
+    BestShortSideFit,
+    /// Minimizes the longer leftover side of the free rectangle, then the shorter one.
+    BestLongSideFit,
+    /// Minimizes the unused area of the free rectangle, then the shorter leftover side.
+    BestAreaFit,
+    /// Places the box as deep into the bin as possible, then as far left as possible.
+    BottomLeft,
+    /// Maximizes the length of the box's edges touching the bin or other placed boxes.
+    ContactPoint,
+}
+
+impl Heuristic {
+    /// Every built-in heuristic, in declaration order.
+    pub const ALL: [Heuristic; 5] = [
+        Heuristic::BestShortSideFit,
+        Heuristic::BestLongSideFit,
+        Heuristic::BestAreaFit,
+        Heuristic::BottomLeft,
+        Heuristic::ContactPoint,
+    ];
+}
+
+impl PlacementHeuristic for Heuristic {
+    fn score(&self, candidate: &Bucket, free: &Bucket, context: &PlacementContext) -> (i64, i64) {
+        let leftover_x = (free.width - candidate.width) as i64;
+        let leftover_y = (free.height - candidate.height) as i64;
+        let short_side = i64::min(leftover_x, leftover_y);
+        let long_side = i64::max(leftover_x, leftover_y);
+
+        match self {
+            Heuristic::BestShortSideFit => (short_side, long_side),
+            Heuristic::BestLongSideFit => (long_side, short_side),
+            Heuristic::BestAreaFit => (free.area() - candidate.area(), short_side),
+            // Boxes settle towards the bottom of a bin, which is the larger y coordinate.
+            Heuristic::BottomLeft => (-(candidate.originy as i64), candidate.originx as i64),
+            Heuristic::ContactPoint => (-contact_length(candidate, context), 0),
+        }
+    }
+}
+
+/// Returns the length of overlap between the segments `[a1, a2]` and `[b1, b2]`.
+fn common_interval(a1: i32, a2: i32, b1: i32, b2: i32) -> i32 {
+    if a2 < b1 || b2 < a1 {
+        return 0;
+    }
+    i32::min(a2, b2) - i32::max(a1, b1)
+}
+
+/// Sums the lengths of the candidate's edges that touch the bin's edges or placed boxes.
+fn contact_length(candidate: &Bucket, context: &PlacementContext) -> i64 {
+    let (x1, x2, y1, y2) = candidate.get_coords();
+    let (bx1, bx2, by1, by2) = context.container.get_coords();
+    let mut score = 0;
+
+    if x1 == bx1 || x2 == bx2 {
+        score += candidate.height as i64;
+    }
+    if y1 == by1 || y2 == by2 {
+        score += candidate.width as i64;
+    }
+
+    for placed in context
+        .placed
+        .iter()
+        .filter(|b| b.bucketid == Some(candidate.bucketid))
+    {
+        let (px1, px2, py1, py2) = placed.get_coords();
+        if px2 == x1 || px1 == x2 {
+            score += common_interval(py1, py2, y1, y2) as i64;
+        }
+        if py2 == y1 || py1 == y2 {
+            score += common_interval(px1, px2, x1, x2) as i64;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(placed: &'a [PackingBox], container: &'a Bucket) -> PlacementContext<'a> {
+        PlacementContext { placed, container }
+    }
+
+    #[test]
+    fn test_short_and_long_side_fit() {
+        let container = Bucket::new(10, 20, 0, 0, 1);
+        let free = Bucket::new(10, 20, 0, 0, 1);
+        let candidate = Bucket::new(4, 18, 0, 2, 1);
+        let ctx = context(&[], &container);
+
+        assert_eq!(
+            Heuristic::BestShortSideFit.score(&candidate, &free, &ctx),
+            (2, 6)
+        );
+        assert_eq!(
+            Heuristic::BestLongSideFit.score(&candidate, &free, &ctx),
+            (6, 2)
+        );
+        assert_eq!(
+            Heuristic::BestAreaFit.score(&candidate, &free, &ctx),
+            (128, 2)
+        );
+    }
+
+    #[test]
+    fn test_area_fit_on_large_sheets() {
+        let container = Bucket::new(100_000, 100_000, 0, 0, 1);
+        let candidate = Bucket::new(1, 1, 0, 99_999, 1);
+        let ctx = context(&[], &container);
+
+        assert_eq!(
+            Heuristic::BestAreaFit.score(&candidate, &container, &ctx),
+            (9_999_999_999, 99_999)
+        );
+    }
+
+    #[test]
+    fn test_bottom_left() {
+        let container = Bucket::new(10, 20, 0, 0, 1);
+        let ctx = context(&[], &container);
+        let free = Bucket::new(10, 20, 0, 0, 1);
+        let deep = Bucket::new(2, 2, 5, 18, 1);
+        let shallow = Bucket::new(2, 2, 0, 10, 1);
+
+        let deep_score = Heuristic::BottomLeft.score(&deep, &free, &ctx);
+        let shallow_score = Heuristic::BottomLeft.score(&shallow, &free, &ctx);
+        assert!(deep_score < shallow_score);
+    }
+
+    #[test]
+    fn test_contact_point() {
+        let container = Bucket::new(10, 10, 0, 0, 1);
+        let mut neighbour = PackingBox::new(5, 5);
+        neighbour.place(0, 5, 1);
+        let placed = vec![neighbour];
+        let ctx = context(&placed, &container);
+        let free = Bucket::new(5, 10, 5, 0, 1);
+
+        // Touches the right and bottom edges of the bin and the neighbour's right edge.
+        let corner = Bucket::new(5, 5, 5, 5, 1);
+        assert_eq!(
+            Heuristic::ContactPoint.score(&corner, &free, &ctx),
+            (-15, 0)
+        );
+    }
+
+    #[test]
+    fn test_common_interval() {
+        assert_eq!(common_interval(0, 5, 3, 8), 2);
+        assert_eq!(common_interval(0, 5, 6, 8), 0);
+    }
+}
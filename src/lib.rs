@@ -10,6 +10,26 @@
 //!
 //! ## Features
 //! - MaxRects bin packing algorithm
+//! - Pluggable placement heuristics (short side, long side, area, bottom-left, contact point)
+//! - Guillotine bin packing, for layouts that can be cut with edge-to-edge cuts
+//! - Skyline bin packing with an optional waste map, for fast online packing
+//! - Shelf bin packing (next fit, first fit and best fit shelves)
+//! - Auto-growing bins that find a tight atlas size
+//! - Search for the smallest single bin that holds a set of boxes
+//! - Opening bins on demand from bin templates with quantities and costs
+//! - A bin-by-bin search mode that minimizes the number of bins used
+//! - A parallel portfolio solver that keeps the best of many configurations
+//! - A simulated annealing optimizer over box order and orientation
+//! - Configurable pre-sorts of the input boxes, including custom comparators
+//! - An exact solver that proves whether small instances fit
+//! - Lower bounds on the number of bins and the optimality gap of a packing
+//! - Spacing between boxes, per-box padding and margins inside bins
+//! - Saw kerf between neighbouring boxes, with the resulting cut coordinates
+//! - Alignment of box origins to multiples, per problem or per box
+//! - Obstacles and keep-out regions inside bins
+//! - Per-box restrictions on the bins a box may be placed in
+//! - A value-maximising mode that drops the least valuable boxes when not everything fits
+//! - Groups of boxes that must share a bin or stay unplaced together
 //! - Visualization of packing solutions
 //!
 //! ## Usage
@@ -64,9 +84,23 @@
 //! Contributions are welcome! Please feel free to open issues and submit pull requests.
 //!
 
+pub mod auto_grow;
+pub mod bounds;
 pub mod bucket;
+pub mod enclosing;
+pub mod exact;
+mod grouping;
+pub mod guillotine;
+pub mod heuristic;
+#[allow(clippy::empty_line_after_doc_comments)]
 pub mod max_rects;
+pub mod on_demand;
+pub mod optimizer;
 pub mod packing_box;
+pub mod portfolio;
+pub mod shelf;
+pub mod skyline;
+pub mod sort;
 pub mod visualizer;
 
 use bucket::Bucket;
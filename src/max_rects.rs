@@ -5,20 +5,104 @@
 /// External crate `rayon` is used to allow parallel processing which optimizes the performance of the algorithm.
 extern crate rayon;
 use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::bucket::Bucket;
-use crate::packing_box::PackingBox;
+use crate::bounds::{Gap, LowerBounds};
+use crate::bucket::{merge_adjacent, Bucket};
+use crate::grouping;
+use crate::heuristic::{Heuristic, PlacementContext, PlacementHeuristic};
+use crate::packing_box::{PackingBox, Placement};
+use crate::portfolio::bins_used;
+use crate::sort::{SortKey, SortStrategy};
 
-/// A `MaxRects` object contains two fields:
+/// How `MaxRects::place` chooses which bins to search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Scores every box against the free space of every bin at once.
+    #[default]
+    Global,
+    /// Fills the bins one at a time in the order they were supplied, moving on to the next
+    /// bin only when the current one cannot take any remaining box. Trailing bins stay empty
+    /// when the boxes fit in fewer.
+    BinByBin,
+    /// Places the boxes one at a time in the order given, each at its best-scoring position,
+    /// so the result depends on how the boxes were sorted.
+    InOrder,
+    /// Aims for the largest total `value` of the placed boxes. The boxes are placed in order
+    /// of value per unit of area and, separately, of value, keeping the better packing; then
+    /// each unplaced box is swapped in for placed boxes whenever that raises the total. This
+    /// is a heuristic and costs far more than the other modes. The unplaced boxes come back
+    /// ordered by how close they came to fitting, closest first.
+    MaxValue,
+}
+
+/// A `Packing` object contains the following fields:
+/// - `placed`: The placed boxes, including any placed earlier through `insert`.
+/// - `unplaced`: The boxes that could not be placed.
+/// - `free`: The free space left in the bins.
+/// - `sort`: The pre-sort that was applied to the boxes, if any.
+/// - `gap`: How far the number of bins used is from the lower bound, if the bins all have
+///   the same size.
+#[derive(Debug, Clone)]
+pub struct Packing {
+    pub placed: Vec<PackingBox>,
+    pub unplaced: Vec<PackingBox>,
+    pub free: Vec<Bucket>,
+    pub sort: Option<SortStrategy>,
+    pub gap: Option<Gap>,
+}
+
+/// Where `MaxRects::best_fit` would put a box.
+struct Fit {
+    /// The heuristic's score for the placement. Lower is better.
+    score: (i64, i64),
+    /// The index of the free rectangle the box goes in.
+    idx: usize,
+    /// The space the box takes up, before any spacing.
+    slot: Bucket,
+    /// Whether the box has to be turned.
+    rotate: bool,
+}
+
+/// A `MaxRects` object contains the following fields:
 /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
-/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `bins`: A vector of `Bucket` objects representing the available free space.
+/// - `containers`: The bins as originally supplied, used by heuristics that need the bin outline.
+/// - `placed`: The boxes placed so far.
+/// - `heuristic`: The rule used to score candidate placements.
+/// - `allow_rotation`: Whether boxes may be turned by 90 degrees to fit. Each box's own
+///   `orientation` can further restrict or force rotation.
+/// - `search`: Whether `place` searches all bins at once or fills them one at a time.
+/// - `sort`: The pre-sort `place` applies to the boxes. Only `SearchMode::InOrder` follows
+///   the sorted order; see `with_sort`.
+/// - `spacing`: The gap kept between placed boxes, unless a box sets its own `padding`.
+/// - `margin`: The strip along each bin's edges that boxes may not be placed in.
+/// - `kerf`: The width of material a saw cut removes between neighbouring boxes.
+/// - `alignment`: The step box origins are snapped to, measured from the bin's origin, unless
+///   a box sets its own `alignment`.
+/// - `round_sizes`: Whether each box also takes up its size rounded up to its alignment.
+#[derive(Clone)]
 pub struct MaxRects {
     pub boxes: Vec<PackingBox>,
     pub bins: Vec<Bucket>,
+    pub containers: Vec<Bucket>,
+    pub placed: Vec<PackingBox>,
+    pub heuristic: Arc<dyn PlacementHeuristic>,
+    pub allow_rotation: bool,
+    pub search: SearchMode,
+    pub sort: Option<SortStrategy>,
+    pub spacing: i32,
+    pub margin: i32,
+    pub kerf: i32,
+    pub alignment: i32,
+    pub round_sizes: bool,
+    /// The bin each group is held to while `place` keeps groups together.
+    pins: HashMap<String, i32>,
 }
 
 impl MaxRects {
-    /// Constructs a new `MaxRects` instance.
+    /// Constructs a new `MaxRects` instance using the default `BestShortSideFit` heuristic.
     ///
     /// # Arguments
     /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
@@ -27,7 +111,568 @@ impl MaxRects {
     /// # Returns
     /// A new `MaxRects` object.
     pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
-        Self { boxes, bins }
+        let mut max_rects = Self {
+            boxes,
+            containers: bins.clone(),
+            bins,
+            placed: vec![],
+            heuristic: Arc::new(Heuristic::default()),
+            allow_rotation: false,
+            search: SearchMode::default(),
+            sort: None,
+            spacing: 0,
+            margin: 0,
+            kerf: 0,
+            alignment: 1,
+            round_sizes: false,
+            pins: HashMap::new(),
+        };
+        if max_rects.has_obstacles() {
+            max_rects.reset_free();
+        }
+        max_rects
+    }
+    /// Constructs a new `MaxRects` instance for a different problem, keeping this packer's
+    /// settings such as its heuristic and rotation switch.
+    ///
+    /// This lets callers configure a packer once and reuse it for many attempts. The solvers
+    /// that take a `packer`, such as `AutoGrow`, `EnclosingBin`, `OnDemand`, `Optimizer` and
+    /// `Portfolio`, use it as such a template: every attempt is a `renew` of it, so its own
+    /// boxes, bins and placed boxes never take part.
+    pub fn renew(&self, boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        let mut renewed = Self {
+            boxes,
+            containers: bins.clone(),
+            bins,
+            placed: vec![],
+            ..self.clone()
+        };
+        if renewed.margin != 0 || renewed.has_obstacles() {
+            renewed.reset_free();
+        }
+        renewed
+    }
+    /// Sets the rule used to score candidate placements.
+    ///
+    /// Accepts one of the built-in `Heuristic`s or any type implementing `PlacementHeuristic`.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, heuristic::Heuristic, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let mut problem = MaxRects::new(vec![PackingBox::new(5, 5)], vec![Bucket::new(10, 10, 0, 0, 1)])
+    ///     .with_heuristic(Heuristic::BestAreaFit);
+    /// let (placed, _, _) = problem.place();
+    /// assert_eq!(placed.len(), 1);
+    /// ```
+    pub fn with_heuristic<H: PlacementHeuristic + 'static>(mut self, heuristic: H) -> Self {
+        self.heuristic = Arc::new(heuristic);
+        self
+    }
+    /// Allows or forbids turning boxes by 90 degrees during placement.
+    ///
+    /// When enabled, both orientations of every box are scored and boxes placed in the swapped
+    /// orientation come back with `rotated` set and their `width` and `height` exchanged.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+    /// Sets whether `place` searches all bins at once or fills them one at a time.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::{MaxRects, SearchMode}, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(5, 5); 4];
+    /// let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)];
+    /// let mut problem = MaxRects::new(boxes, bins).with_search(SearchMode::BinByBin);
+    /// let (placed, _, _) = problem.place();
+    /// assert!(placed.iter().all(|b| b.bucketid == Some(0)));
+    /// ```
+    pub fn with_search(mut self, search: SearchMode) -> Self {
+        self.search = search;
+        self
+    }
+    /// Sorts the boxes with `sort` before placing them.
+    ///
+    /// Only `SearchMode::InOrder` places the boxes in the sorted order, so pair the sort with
+    /// that mode. `Global` and `BinByBin` pick the best-scoring box at every step and
+    /// `MaxValue` orders the boxes by value, so in those modes the sort only breaks ties.
+    ///
+    /// `pack` returns the strategy with the packing.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{
+    ///     bucket::Bucket,
+    ///     max_rects::{MaxRects, SearchMode},
+    ///     packing_box::PackingBox,
+    ///     sort::{SortKey, SortStrategy},
+    /// };
+    ///
+    /// let boxes = vec![PackingBox::new(5, 5), PackingBox::new(10, 10)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+    ///     .with_search(SearchMode::InOrder)
+    ///     .with_sort(SortStrategy::descending(SortKey::Area));
+    /// let (placed, _, _) = problem.place();
+    /// assert_eq!(placed[0].width, 10);
+    /// ```
+    pub fn with_sort(mut self, sort: SortStrategy) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+    /// Keeps a gap of `spacing` between placed boxes, for example for a saw blade or for
+    /// bleed around sprites in a texture atlas.
+    ///
+    /// A box whose `padding` is set keeps that gap instead, and two neighbouring boxes stay
+    /// apart by the larger of their gaps. No gap is kept between a box and the bin's edge;
+    /// use `with_margin` for that.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(4, 10), PackingBox::new(4, 10)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)]).with_spacing(2);
+    /// let (placed, _, _) = problem.place();
+    /// let mut xs: Vec<i32> = placed.iter().map(|b| b.originx.unwrap()).collect();
+    /// xs.sort();
+    /// assert_eq!(xs, vec![0, 6]);
+    /// ```
+    pub fn with_spacing(mut self, spacing: i32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+    /// Keeps boxes at least `margin` away from every edge of their bin.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(8, 8), PackingBox::new(9, 9)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)]).with_margin(1);
+    /// let (placed, unplaced, _) = problem.place();
+    /// assert_eq!((placed[0].originx, placed[0].originy), (Some(1), Some(1)));
+    /// assert_eq!(unplaced.len(), 1);
+    /// ```
+    pub fn with_margin(mut self, margin: i32) -> Self {
+        self.margin = margin;
+        self.reset_free();
+        self
+    }
+    /// Leaves room for a saw blade `kerf` wide between neighbouring boxes.
+    ///
+    /// Unlike `with_spacing`, this models cuts: the blade only runs between boxes, so a box
+    /// may sit flush against the edge of the sheet, and the kerf is added on top of any
+    /// spacing or padding. `cuts` reports where the blade runs.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// // Two 6 wide parts need 12 plus the kerf, so they no longer fit side by side.
+    /// let boxes = vec![PackingBox::new(6, 10), PackingBox::new(6, 10)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(12, 10, 0, 0, 0)]).with_kerf(1);
+    /// let (placed, unplaced, _) = problem.place();
+    /// assert_eq!((placed.len(), unplaced.len()), (1, 1));
+    /// ```
+    pub fn with_kerf(mut self, kerf: i32) -> Self {
+        self.kerf = kerf;
+        self
+    }
+    /// Places every box at x and y offsets from its bin's origin that are multiples of
+    /// `alignment`, as block-compressed textures and some DMA buffers require.
+    ///
+    /// Boxes can override this with their own `alignment`.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(3, 3), PackingBox::new(3, 3)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(16, 16, 0, 0, 0)]).with_alignment(4);
+    /// let (placed, _, _) = problem.place();
+    /// assert!(placed.iter().all(|b| b.originx.unwrap() % 4 == 0 && b.originy.unwrap() % 4 == 0));
+    /// ```
+    pub fn with_alignment(mut self, alignment: i32) -> Self {
+        self.alignment = alignment;
+        self
+    }
+    /// Rounds the space each box takes up to a multiple of its alignment, so the boxes after
+    /// it stay aligned without wasting the remainder. Reported sizes are not rounded.
+    pub fn with_round_sizes(mut self, round_sizes: bool) -> Self {
+        self.round_sizes = round_sizes;
+        self
+    }
+    /// Returns the strips of material the saw removes to cut the placed boxes free.
+    ///
+    /// Every edge of a box that does not lie on the edge of its bin is cut, and the blade
+    /// runs on the outside of the box so the box keeps its full size. Strips along the same
+    /// line are joined into one cut. Without a kerf the strips have no width and only mark
+    /// the cut lines.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(6, 10), PackingBox::new(5, 10)];
+    /// let mut problem = MaxRects::new(boxes, vec![Bucket::new(12, 10, 0, 0, 0)]).with_kerf(1);
+    /// problem.place();
+    ///
+    /// let cuts = problem.cuts();
+    /// assert_eq!(cuts.len(), 1);
+    /// assert_eq!(cuts[0].get_coords(), (6, 7, 0, 10));
+    /// ```
+    pub fn cuts(&self) -> Vec<Bucket> {
+        let mut strips: Vec<Bucket> = vec![];
+        for box_item in &self.placed {
+            let (Some(originx), Some(originy), Some(bucketid)) =
+                (box_item.originx, box_item.originy, box_item.bucketid)
+            else {
+                continue;
+            };
+            let body = Bucket::new(box_item.width, box_item.height, originx, originy, bucketid);
+            let container = self.outline(&body);
+            let (x1, x2, y1, y2) = body.get_coords();
+            let (cx1, cx2, cy1, cy2) = container.get_coords();
+            let edges = [
+                (x1 > cx1, (x1 - self.kerf).max(cx1), y1, x1, y2),
+                (x2 < cx2, x2, y1, (x2 + self.kerf).min(cx2), y2),
+                (y1 > cy1, x1, (y1 - self.kerf).max(cy1), x2, y1),
+                (y2 < cy2, x1, y2, x2, (y2 + self.kerf).min(cy2)),
+            ];
+            for (inside, left, top, right, bottom) in edges {
+                if inside {
+                    strips.push(Bucket::new(
+                        right - left,
+                        bottom - top,
+                        left,
+                        top,
+                        container.bucketid,
+                    ));
+                }
+            }
+        }
+        merge_adjacent(&mut strips);
+        strips
+    }
+    /// Returns the part of `container` boxes may be placed in, inside the margin.
+    fn usable(&self, container: &Bucket) -> Option<Bucket> {
+        let width = container.width - 2 * self.margin;
+        let height = container.height - 2 * self.margin;
+        (width > 0 && height > 0).then(|| Bucket {
+            width,
+            height,
+            originx: container.originx + self.margin,
+            originy: container.originy + self.margin,
+            obstacles: vec![],
+            ..container.clone()
+        })
+    }
+    /// Whether any bin has obstacles to keep out of the free space.
+    fn has_obstacles(&self) -> bool {
+        self.containers.iter().any(|bin| !bin.obstacles.is_empty())
+    }
+    /// Recomputes the free rectangles of every bin.
+    fn reset_free(&mut self) {
+        let mut ids: Vec<i32> = self.containers.iter().map(|bin| bin.bucketid).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            self.rebuild_free(id);
+        }
+    }
+    /// Returns the step the origin of `box_item` is snapped to.
+    fn alignment(&self, box_item: &PackingBox) -> i32 {
+        box_item.alignment.unwrap_or(self.alignment).max(1)
+    }
+    /// Returns the space a box `width` by `height` takes up, rounded up to `alignment` if
+    /// sizes are rounded.
+    fn extent(&self, alignment: i32, width: i32, height: i32) -> (i32, i32) {
+        match self.round_sizes {
+            true => (round_up(width, alignment), round_up(height, alignment)),
+            false => (width, height),
+        }
+    }
+    /// Returns the slot a box `width` by `height` takes in the free rectangle `rect`: as far
+    /// left and as low as `alignment` allows.
+    fn slot(
+        &self,
+        rect: &Bucket,
+        container: &Bucket,
+        alignment: i32,
+        width: i32,
+        height: i32,
+    ) -> Option<Bucket> {
+        let (width, height) = self.extent(alignment, width, height);
+        let bottom = rect.originy + rect.height - height;
+        let x = container.originx + round_up(rect.originx - container.originx, alignment);
+        let y = container.originy + round_down(bottom - container.originy, alignment);
+        (x + width <= rect.originx + rect.width && y >= rect.originy)
+            .then(|| Bucket::new(width, height, x, y, rect.bucketid))
+    }
+    /// Returns the gap kept around `box_item`, including the kerf.
+    fn padding(&self, box_item: &PackingBox) -> i32 {
+        box_item.padding.unwrap_or(self.spacing) + self.kerf
+    }
+    /// Returns the space a box occupying `body` takes away from the free rectangles: the body
+    /// grown by `padding` to the right and upwards, clipped to `usable`.
+    ///
+    /// Growing every box on the same two sides keeps the gap on all four, because a box
+    /// placed later below or to the left of it must fit its own gap next to it.
+    fn reserve(body: &Bucket, padding: i32, usable: &Bucket) -> Bucket {
+        let (x1, x2, y1, y2) = body.get_coords();
+        let (_, right_edge, top_edge, _) = usable.get_coords();
+        let right = i32::max(x2, i32::min(x2 + padding, right_edge));
+        let top = i32::min(y1, i32::max(y1 - padding, top_edge));
+        Bucket::new(right - x1, y2 - top, x1, top, body.bucketid)
+    }
+    /// Whether `body` is far enough from every box already placed in its bin.
+    ///
+    /// The free rectangles only account for each placed box's own gap, so this is needed
+    /// when boxes override the spacing with a larger `padding` than their neighbours.
+    fn keeps_clear(&self, body: &Bucket, padding: i32) -> bool {
+        self.placed
+            .iter()
+            .filter(|other| other.bucketid == Some(body.bucketid))
+            .all(|other| {
+                let gap = i32::max(padding, self.padding(other));
+                let reach = Bucket::new(
+                    body.width + 2 * gap,
+                    body.height + 2 * gap,
+                    body.originx - gap,
+                    body.originy - gap,
+                    body.bucketid,
+                );
+                !other.overlap(&reach)
+            })
+    }
+    /// Returns the bin `box_item` has to go in to join its group: the bin of a member that is
+    /// already placed, or else the bin the group is held to.
+    fn home(&self, box_item: &PackingBox) -> Option<i32> {
+        grouping::home(box_item, &self.placed, &self.pins)
+    }
+    /// Returns the original bin `rect` lies in.
+    ///
+    /// Several bins may share a `bucketid`, so this is the one containing `rect`, falling
+    /// back to the first bin with its `bucketid` and then to `rect` itself.
+    fn outline<'a>(&'a self, rect: &'a Bucket) -> &'a Bucket {
+        self.containers
+            .iter()
+            .find(|bin| bin.contains(rect))
+            .or_else(|| {
+                self.containers
+                    .iter()
+                    .find(|bin| bin.bucketid == rect.bucketid)
+            })
+            .unwrap_or(rect)
+    }
+    /// Finds the best free rectangle and orientation for `box_item`.
+    ///
+    /// # Returns
+    /// The score, the free rectangle, the slot the box takes in it and whether the box has to
+    /// be turned.
+    fn best_fit(&self, box_item: &PackingBox, bucketid: Option<i32>) -> Option<Fit> {
+        let home = self.home(box_item);
+        let padding = self.padding(box_item);
+        let alignment = self.alignment(box_item);
+        let mixed = box_item.padding.is_some() || self.placed.iter().any(|b| b.padding.is_some());
+        self.bins
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| {
+                bucketid.is_none_or(|id| rect.bucketid == id)
+                    && home.is_none_or(|id| rect.bucketid == id)
+                    && box_item.allows(rect.bucketid)
+            })
+            .flat_map(|(j, rect)| {
+                let container = self.outline(rect);
+                box_item
+                    .orientations(self.allow_rotation, container.grain)
+                    .into_iter()
+                    .map(move |(width, height, rotate)| (j, rect, container, width, height, rotate))
+            })
+            .filter_map(|(j, rect, container, width, height, rotate)| {
+                if width <= rect.width && height <= rect.height {
+                    let candidate = self.slot(rect, container, alignment, width, height)?;
+                    let usable = self.usable(container).unwrap_or_else(|| rect.clone());
+                    if !rect.contains(&Self::reserve(&candidate, padding, &usable))
+                        || (mixed && !self.keeps_clear(&candidate, padding))
+                    {
+                        return None;
+                    }
+                    let context = PlacementContext {
+                        placed: &self.placed,
+                        container,
+                    };
+                    Some(Fit {
+                        score: self.heuristic.score(&candidate, rect, &context),
+                        idx: j,
+                        slot: candidate,
+                        rotate,
+                    })
+                } else {
+                    None
+                }
+            })
+            .min_by(|a, b| a.score.cmp(&b.score))
+    }
+    /// Places `box_item` in the slot `best_fit` chose and carves the space it uses out of
+    /// every free rectangle it overlaps.
+    fn commit(&mut self, mut box_item: PackingBox, fit: Fit) -> PackingBox {
+        if fit.rotate {
+            box_item.rotate();
+        }
+        let bin_item = self.bins.remove(fit.idx);
+        box_item.place(fit.slot.originx, fit.slot.originy, bin_item.bucketid);
+        let used = self.footprint(&box_item);
+
+        self.bins.extend(bin_item.split(&used));
+        Self::carve(&mut self.bins, &used);
+        self.prune();
+
+        self.placed.push(box_item.clone());
+        box_item
+    }
+    /// Returns the space a placed box takes away from the free rectangles, including its gap.
+    fn footprint(&self, box_item: &PackingBox) -> Bucket {
+        let (width, height) =
+            self.extent(self.alignment(box_item), box_item.width, box_item.height);
+        let body = Bucket::new(
+            width,
+            height,
+            box_item.originx.unwrap(),
+            box_item.originy.unwrap(),
+            box_item.bucketid.unwrap(),
+        );
+        let usable = self
+            .usable(self.outline(&body))
+            .unwrap_or_else(|| body.clone());
+        Self::reserve(&body, self.padding(box_item), &usable)
+    }
+    /// Splits every rectangle in `free` that overlaps `used` into the pieces left around it.
+    fn carve(free: &mut Vec<Bucket>, used: &Bucket) {
+        let mut pieces = vec![];
+        free.retain(|rect| {
+            if rect.overlap(used) {
+                pieces.extend(rect.split(used));
+                false
+            } else {
+                true
+            }
+        });
+        free.extend(pieces);
+    }
+    /// Removes free rectangles that are contained in another free rectangle.
+    fn prune(&mut self) {
+        let mut contained = vec![false; self.bins.len()];
+
+        for (i, bin1) in self.bins.iter().enumerate() {
+            for (j, bin2) in self.bins.iter().enumerate() {
+                // Of two identical rectangles only the later one is dropped.
+                if i != j && bin1.contains(bin2) && (i < j || !bin2.contains(bin1)) {
+                    contained[j] = true;
+                }
+            }
+        }
+
+        let mut contained = contained.into_iter();
+        self.bins.retain(|_| !contained.next().unwrap());
+    }
+    /// Places a single box into the current free space, updating the free rectangles
+    /// immediately.
+    ///
+    /// This is meant for packers that receive boxes one at a time, such as runtime texture
+    /// atlases. The placed box is added to `placed`.
+    ///
+    /// # Returns
+    /// Where the box was placed, or `None` if it does not fit anywhere.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let mut atlas = MaxRects::new(vec![], vec![Bucket::new(64, 64, 0, 0, 0)]);
+    /// let placement = atlas.insert(PackingBox::new(16, 16)).unwrap();
+    /// assert_eq!((placement.originx, placement.originy), (0, 48));
+    /// assert!(atlas.insert(PackingBox::new(128, 16)).is_none());
+    /// ```
+    pub fn insert(&mut self, box_item: PackingBox) -> Option<Placement> {
+        let fit = self.best_fit(&box_item, None)?;
+        self.commit(box_item, fit).placement()
+    }
+    /// Adds a bin to a live packer, making its space available to later placements.
+    pub fn add_bin(&mut self, bin: Bucket) {
+        let bucketid = bin.bucketid;
+        self.containers.push(bin);
+        self.rebuild_free(bucketid);
+    }
+    /// Removes a previously placed box and returns its space to the free rectangles.
+    ///
+    /// The free space of the box's bin is rebuilt from the boxes still placed there, so the
+    /// freed area is merged with its neighbours into maximal free rectangles and can be reused
+    /// by larger boxes.
+    ///
+    /// # Returns
+    /// The removed box, or `None` if no placed box matches `placement`.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let mut atlas = MaxRects::new(vec![], vec![Bucket::new(10, 10, 0, 0, 0)]);
+    /// let left = atlas.insert(PackingBox::new(5, 10)).unwrap();
+    /// atlas.insert(PackingBox::new(5, 10)).unwrap();
+    ///
+    /// atlas.remove(&left);
+    /// assert!(atlas.insert(PackingBox::new(5, 10)).is_some());
+    /// ```
+    pub fn remove(&mut self, placement: &Placement) -> Option<PackingBox> {
+        let idx = self
+            .placed
+            .iter()
+            .position(|b| b.placement().as_ref() == Some(placement))?;
+        let removed = self.placed.remove(idx);
+        self.rebuild_free(placement.bucketid);
+        Some(removed)
+    }
+    /// Recomputes the free rectangles of one bin from its original outline, its obstacles
+    /// and the boxes currently placed in it.
+    fn rebuild_free(&mut self, bucketid: i32) {
+        let bins: Vec<&Bucket> = self
+            .containers
+            .iter()
+            .filter(|bin| bin.bucketid == bucketid)
+            .collect();
+        let mut free: Vec<Bucket> = bins.iter().filter_map(|bin| self.usable(bin)).collect();
+        for obstacle in bins.iter().flat_map(|bin| &bin.obstacles) {
+            // Obstacles follow the bin when it is given a new id.
+            let obstacle = Bucket {
+                bucketid,
+                ..obstacle.clone()
+            };
+            Self::carve(&mut free, &obstacle);
+        }
+        for box_item in self.placed.iter().filter(|b| b.bucketid == Some(bucketid)) {
+            Self::carve(&mut free, &self.footprint(box_item));
+        }
+
+        self.bins.retain(|bin| bin.bucketid != bucketid);
+        self.bins.extend(free);
+        self.prune();
+    }
+    /// Merges free rectangles that together form a single rectangle and drops any that are
+    /// contained in another.
+    ///
+    /// Long-running packers, or packers whose free list has been edited by hand, can call
+    /// this periodically to recover large free regions.
+    ///
+    /// # Returns
+    /// The number of merges performed.
+    pub fn defragment(&mut self) -> usize {
+        let merges = merge_adjacent(&mut self.bins);
+        self.prune();
+        merges
     }
     /// Attempts to place the boxes into the bins.
     ///
@@ -37,162 +682,273 @@ impl MaxRects {
     ///
     /// # Returns
     /// A tuple of three vectors:
-    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the placed boxes, including any placed
+    ///   earlier through `insert`.
     /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
     /// - A vector of `Bucket` objects representing the updated bins after all possible placements have been made.
+    ///
+    /// Boxes that share a `group` all end up in the same bin, or all stay unplaced.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, max_rects::MaxRects, packing_box::PackingBox};
+    ///
+    /// let mut boxes = vec![PackingBox::new(5, 5).with_group("walk"); 3];
+    /// boxes.push(PackingBox::new(5, 5));
+    /// let bins = vec![Bucket::new(10, 5, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)];
+    /// let (placed, _, _) = MaxRects::new(boxes, bins).place();
+    ///
+    /// let walk: Vec<_> = placed.iter().filter(|b| b.group.is_some()).collect();
+    /// assert_eq!(walk.len(), 3);
+    /// assert!(walk.iter().all(|b| b.bucketid == Some(1)));
+    /// ```
     pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
-        let mut nochange = false;
-        let mut placed = vec![];
-
-        while !nochange {
-            nochange = true;
-
-            let search_result: Option<(i32, usize, usize)> = self
-                .boxes
-                .par_iter()
-                .enumerate()
-                .filter_map(|(i, box_item)| {
-                    self.bins
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(j, rect)| {
-                            if box_item.width <= rect.width && box_item.height <= rect.height {
-                                let diff = i32::min(
-                                    rect.width - box_item.width,
-                                    rect.height - box_item.height,
-                                );
-                                Some((diff, i, j))
-                            } else {
-                                None
-                            }
-                        })
-                        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
-                })
-                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-
-            if let Some((_, box_idx, idx)) = search_result {
-                nochange = false;
-                let mut box_item = self.boxes.remove(box_idx);
-                let bin_item = self.bins.remove(idx);
-
-                let adjacent = Bucket {
-                    width: bin_item.width - box_item.width,
-                    height: bin_item.height,
-                    originx: bin_item.originx + box_item.width,
-                    originy: bin_item.originy,
-                    bucketid: bin_item.bucketid,
-                };
-
-                let above = Bucket {
-                    width: bin_item.width,
-                    height: bin_item.height - box_item.height,
-                    originx: bin_item.originx,
-                    originy: bin_item.originy,
-                    bucketid: bin_item.bucketid,
-                };
-
-                box_item.place(
-                    bin_item.originx,
-                    bin_item.height - box_item.height + bin_item.originy,
-                    bin_item.bucketid,
-                );
-                placed.push(box_item.clone());
+        if self.boxes.iter().any(|b| b.group.is_some()) {
+            self.run_grouped();
+        } else {
+            self.run();
+        }
 
-                if adjacent.area() > 0 {
-                    self.bins.push(adjacent);
-                }
+        (self.placed.clone(), self.boxes.clone(), self.bins.clone())
+    }
+    /// Places the boxes like `place` and returns the result together with the pre-sort that
+    /// was applied, so the run can be reproduced, and the gap to the lower bound.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{
+    ///     bucket::Bucket,
+    ///     max_rects::{MaxRects, SearchMode},
+    ///     packing_box::PackingBox,
+    ///     sort::{SortKey, SortStrategy},
+    /// };
+    ///
+    /// let boxes = vec![PackingBox::new(5, 5), PackingBox::new(10, 10)];
+    /// let packing = MaxRects::new(boxes, vec![Bucket::new(10, 20, 0, 0, 0)])
+    ///     .with_search(SearchMode::InOrder)
+    ///     .with_sort(SortStrategy::descending(SortKey::Area))
+    ///     .pack();
+    /// assert_eq!(packing.placed.len(), 2);
+    /// assert_eq!(packing.sort, Some(SortStrategy::descending(SortKey::Area)));
+    /// assert_eq!(packing.gap.unwrap().absolute, 0);
+    /// ```
+    pub fn pack(&mut self) -> Packing {
+        let boxes: Vec<PackingBox> = self.placed.iter().chain(&self.boxes).cloned().collect();
+        let bounds = LowerBounds::for_bins(&boxes, &self.containers, self.allow_rotation);
+        let (placed, unplaced, free) = self.place();
+        Packing {
+            gap: bounds.map(|b| b.gap(bins_used(&placed))),
+            placed,
+            unplaced,
+            free,
+            sort: self.sort.clone(),
+        }
+    }
+    /// Places the boxes with the configured pre-sort and search mode.
+    fn run(&mut self) {
+        if let Some(sort) = &self.sort {
+            sort.apply(&mut self.boxes);
+        }
 
-                if above.area() > 0 {
-                    self.bins.push(above);
+        match self.search {
+            SearchMode::Global => self.fill(None),
+            SearchMode::BinByBin => {
+                for bucketid in grouping::bin_order(&self.containers) {
+                    self.fill(Some(bucketid));
                 }
+            }
+            SearchMode::InOrder => self.fill_in_order(),
+            SearchMode::MaxValue => {
+                self.fill_by_value();
+                self.order_unplaced();
+            }
+        }
+    }
+    /// Places the boxes so that every group ends up in a single bin or not at all.
+    ///
+    /// A box always joins the bin its group's first placed member went to. When only part
+    /// of a group fits there, the group is held to the next bin all its members may go in
+    /// and the packing starts over; a group that fits whole in no bin is left unplaced.
+    fn run_grouped(&mut self) {
+        let start = self.clone();
+        let order = grouping::bin_order(&self.containers);
+        let (_, unplaced, _) = grouping::place_grouped(&start.boxes, &order, |boxes, pins| {
+            *self = start.clone();
+            self.boxes = boxes;
+            self.pins = pins.clone();
+            self.run();
+            (self.placed.clone(), self.boxes.clone(), self.bins.clone())
+        });
 
-                let mut overlaps = vec![false; self.bins.len()];
-                let mut new_buckets = Vec::new();
-
-                for (idx, rect) in self.bins.iter().enumerate() {
-                    if placed.last().unwrap().overlap(rect) {
-                        overlaps[idx] = true;
-                        // split!
-                        let left = Bucket::new(
-                            placed.last().unwrap().originx.unwrap() - rect.originx,
-                            rect.height,
-                            rect.originx,
-                            rect.originy,
-                            rect.bucketid,
-                        );
-                        let above = Bucket::new(
-                            rect.width,
-                            placed.last().unwrap().originy.unwrap() - rect.originy,
-                            rect.originx,
-                            rect.originy,
-                            rect.bucketid,
-                        );
-                        let right = Bucket::new(
-                            rect.get_coords().1 - placed.last().unwrap().get_coords().1,
-                            rect.height,
-                            placed.last().unwrap().get_coords().1,
-                            rect.originy,
-                            rect.bucketid,
-                        );
-                        let below = Bucket::new(
-                            rect.width,
-                            rect.get_coords().3 - placed.last().unwrap().get_coords().3,
-                            rect.originx,
-                            placed.last().unwrap().get_coords().3,
-                            rect.bucketid,
-                        );
-
-                        for new_bucket in [left, above, right, below] {
-                            if new_bucket.area() > 0 {
-                                new_buckets.push(new_bucket);
-                                overlaps.push(false);
-                            }
-                        }
-                    }
+        self.pins.clear();
+        self.boxes = unplaced;
+        if self.search == SearchMode::MaxValue {
+            self.order_unplaced();
+        }
+    }
+    /// Places the boxes one at a time in their current order, keeping those that do not fit.
+    fn fill_in_order(&mut self) {
+        for box_item in std::mem::take(&mut self.boxes) {
+            match self.best_fit(&box_item, None) {
+                Some(fit) => {
+                    self.commit(box_item, fit);
                 }
-
-                self.bins.extend(new_buckets);
-                let filtered_bins: Vec<Bucket> = self
-                    .bins
-                    .iter()
-                    .zip(&overlaps)
-                    .filter_map(|(item, &keep)| if !keep { Some(item) } else { None })
-                    .cloned()
-                    .collect();
-
-                self.bins = filtered_bins;
-                let mut overlaps = vec![false; self.bins.len()];
-
-                for (i, bin1) in self.bins.iter().enumerate() {
-                    for (j, bin2) in self.bins.iter().enumerate() {
-                        if i != j && bin1.contains(bin2) {
-                            overlaps[j] = true;
-                        }
-                    }
+                None => self.boxes.push(box_item),
+            }
+        }
+    }
+    /// Packs for the largest total value: one greedy pass by value density and one by value,
+    /// keeping the better, followed by `swap_in_value`.
+    fn fill_by_value(&mut self) {
+        let start = self.clone();
+        let mut best: Option<MaxRects> = None;
+        for key in [SortKey::ValueDensity, SortKey::Value] {
+            let mut attempt = start.clone();
+            SortStrategy::descending(key).apply(&mut attempt.boxes);
+            attempt.fill_in_order();
+            if best
+                .as_ref()
+                .is_none_or(|best| attempt.placed_value() > best.placed_value())
+            {
+                best = Some(attempt);
+            }
+        }
+        if let Some(best) = best {
+            *self = best;
+        }
+        self.swap_in_value(start.placed.len());
+    }
+    /// Returns the total value of the placed boxes.
+    fn placed_value(&self) -> f64 {
+        self.placed.iter().map(|b| b.value()).sum()
+    }
+    /// Tries each unplaced box, most valuable first, in place of each box placed by this run:
+    /// the placed box is taken out, the unplaced one put in its bin, and the other unplaced
+    /// boxes placed again by value. A swap is kept when it raises the total value.
+    ///
+    /// The first `fixed` placed boxes were placed earlier and are never taken out. Boxes in
+    /// groups are left alone so that groups stay whole.
+    fn swap_in_value(&mut self, fixed: usize) {
+        // Every kept swap raises the total, and this bounds how many are looked for.
+        let mut swaps = self.boxes.len() + self.placed.len();
+        SortStrategy::descending(SortKey::Value).apply(&mut self.boxes);
+        let mut i = 0;
+        while i < self.boxes.len() && swaps > 0 {
+            let incoming = &self.boxes[i];
+            let area = |b: &PackingBox| b.width as i64 * b.height as i64;
+            let swapped = (fixed..self.placed.len())
+                .filter(|&j| incoming.group.is_none() && self.placed[j].group.is_none())
+                .filter(|&j| {
+                    // The incoming box needs the outgoing box's space and the bin's free area.
+                    let bucketid = self.placed[j].bucketid;
+                    let used: i64 = self
+                        .placed
+                        .iter()
+                        .filter(|b| b.bucketid == bucketid)
+                        .map(area)
+                        .sum();
+                    let size: i64 = self
+                        .containers
+                        .iter()
+                        .filter(|bin| Some(bin.bucketid) == bucketid)
+                        .map(|bin| bin.area())
+                        .sum();
+                    area(incoming) <= size - used + area(&self.placed[j])
+                })
+                .find_map(|j| {
+                    let mut trial = self.clone();
+                    let placement = trial.placed[j].placement()?;
+                    let outgoing = trial.remove(&placement)?;
+                    let incoming = trial.boxes.remove(i);
+                    let fit = trial.best_fit(&incoming, Some(placement.bucketid))?;
+                    trial.commit(incoming, fit);
+                    trial.boxes.push(outgoing);
+                    SortStrategy::descending(SortKey::Value).apply(&mut trial.boxes);
+                    trial.fill_in_order();
+                    (trial.placed_value() > self.placed_value()).then_some(trial)
+                });
+            match swapped {
+                Some(trial) => {
+                    *self = trial;
+                    swaps -= 1;
+                    i = 0;
                 }
-
-                let new_bins: Vec<Bucket> = self
-                    .bins
-                    .clone()
+                None => i += 1,
+            }
+        }
+    }
+    /// Orders the unplaced boxes by how close they came to fitting, closest first, keeping
+    /// the more valuable of two equally close boxes first.
+    fn order_unplaced(&mut self) {
+        SortStrategy::descending(SortKey::Value).apply(&mut self.boxes);
+        let mut unplaced: Vec<(f64, PackingBox)> = std::mem::take(&mut self.boxes)
+            .into_iter()
+            .map(|box_item| (self.nearest_fit(&box_item), box_item))
+            .collect();
+        unplaced.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.boxes = unplaced.into_iter().map(|(_, box_item)| box_item).collect();
+    }
+    /// Returns how close `box_item` comes to fitting: the largest share of its area that
+    /// any free rectangle it is allowed into can hold, in any orientation it may take.
+    fn nearest_fit(&self, box_item: &PackingBox) -> f64 {
+        let area = (box_item.width as i64 * box_item.height as i64) as f64;
+        let home = self.home(box_item);
+        self.bins
+            .iter()
+            .filter(|rect| {
+                home.is_none_or(|id| rect.bucketid == id) && box_item.allows(rect.bucketid)
+            })
+            .flat_map(|rect| {
+                box_item
+                    .orientations(self.allow_rotation, self.outline(rect).grain)
                     .into_iter()
-                    .enumerate()
-                    .filter(|(idx, _)| !overlaps[*idx])
-                    .map(|(_, bin)| bin)
-                    .collect();
+                    .map(move |(width, height, _)| {
+                        let covered = i32::min(width, rect.width) as i64
+                            * i32::min(height, rect.height) as i64;
+                        covered as f64 / area
+                    })
+            })
+            .fold(0.0, f64::max)
+    }
+    /// Repeatedly places the best-scoring box until no box fits, searching only the bin
+    /// `bucketid` if given.
+    fn fill(&mut self, bucketid: Option<i32>) {
+        loop {
+            let search_result = self
+                .boxes
+                .par_iter()
+                .enumerate()
+                .filter_map(|(i, box_item)| self.best_fit(box_item, bucketid).map(|fit| (i, fit)))
+                .min_by(|a, b| a.1.score.cmp(&b.1.score));
 
-                self.bins = new_bins;
+            match search_result {
+                Some((box_idx, fit)) => {
+                    let box_item = self.boxes.remove(box_idx);
+                    self.commit(box_item, fit);
+                }
+                None => break,
             }
         }
-
-        (placed, self.boxes.clone(), self.bins.clone())
     }
 }
 
+/// Rounds `value` up to a multiple of `step`.
+fn round_up(value: i32, step: i32) -> i32 {
+    value + (step - value.rem_euclid(step)) % step
+}
+
+/// Rounds `value` down to a multiple of `step`.
+fn round_down(value: i32, step: i32) -> i32 {
+    value - value.rem_euclid(step)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::bucket::Bucket;
-    use crate::packing_box::PackingBox;
+    use crate::bucket::GrainAxis;
+    use crate::heuristic::{Heuristic, PlacementContext, PlacementHeuristic};
+    use crate::packing_box::{Orientation, PackingBox, Placement};
 
     #[test]
     fn test_new() {
@@ -237,6 +993,690 @@ mod tests {
         assert_eq!(updated_bins[2].get_coords(), (5, 10, 0, 16)); // new buckets
     }
 
+    #[test]
+    fn test_place_with_heuristics() {
+        for heuristic in Heuristic::ALL {
+            let boxes = vec![
+                PackingBox::new(5, 6),
+                PackingBox::new(4, 4),
+                PackingBox::new(10, 3),
+            ];
+            let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+            let mut max_rects = MaxRects::new(boxes, bins).with_heuristic(heuristic);
+            let (placed, remaining, _) = max_rects.place();
+
+            assert_eq!(placed.len(), 3, "{:?}", heuristic);
+            assert!(remaining.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_place_bottom_left_fills_bottom_row() {
+        let boxes = vec![PackingBox::new(3, 3), PackingBox::new(3, 3)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_heuristic(Heuristic::BottomLeft);
+        let (placed, _, _) = max_rects.place();
+
+        assert_eq!(placed[0].get_coords(), (0, 3, 17, 20));
+        assert_eq!(placed[1].get_coords(), (3, 6, 17, 20));
+    }
+
+    #[test]
+    fn test_place_custom_heuristic() {
+        // Prefers the free rectangle furthest to the right.
+        struct RightMost;
+        impl PlacementHeuristic for RightMost {
+            fn score(&self, candidate: &Bucket, _: &Bucket, _: &PlacementContext) -> (i64, i64) {
+                (-(candidate.originx as i64), 0)
+            }
+        }
+
+        let boxes = vec![PackingBox::new(3, 3), PackingBox::new(3, 3)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_heuristic(RightMost);
+        let (placed, _, _) = max_rects.place();
+
+        assert_eq!(placed[1].originx, Some(3));
+    }
+
+    #[test]
+    fn test_place_rotated() {
+        let boxes = vec![PackingBox::new(20, 10)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+
+        let mut fixed = MaxRects::new(boxes.clone(), bins.clone());
+        let (placed, remaining, _) = fixed.place();
+        assert!(placed.is_empty());
+        assert_eq!(remaining.len(), 1);
+
+        let mut rotating = MaxRects::new(boxes, bins).with_rotation(true);
+        let (placed, remaining, updated_bins) = rotating.place();
+        assert!(remaining.is_empty());
+        assert!(placed[0].rotated);
+        assert_eq!(placed[0].get_coords(), (0, 10, 0, 20));
+        assert!(updated_bins.is_empty());
+    }
+
+    #[test]
+    fn test_place_prefers_better_orientation() {
+        let boxes = vec![PackingBox::new(4, 10)];
+        let bins = vec![Bucket::new(10, 4, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_rotation(true);
+        let (placed, _, _) = max_rects.place();
+
+        assert!(placed[0].rotated);
+        assert_eq!((placed[0].width, placed[0].height), (10, 4));
+    }
+
+    #[test]
+    fn test_place_respects_orientation() {
+        let boxes = vec![PackingBox::new(20, 10).with_orientation(Orientation::Fixed)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_rotation(true);
+        let (placed, remaining, _) = max_rects.place();
+
+        assert!(placed.is_empty());
+        assert_eq!(remaining.len(), 1);
+    }
+
+    #[test]
+    fn test_place_follows_grain() {
+        let boxes = vec![
+            PackingBox::new(4, 8).with_orientation(Orientation::Grain(GrainAxis::Vertical)),
+            PackingBox::new(8, 4).with_orientation(Orientation::Grain(GrainAxis::Horizontal)),
+            PackingBox::new(4, 8),
+        ];
+        let bins = vec![Bucket::new(20, 20, 0, 0, 1).with_grain(GrainAxis::Horizontal)];
+        // Grain constraints apply even with the global rotation switch off.
+        let mut max_rects = MaxRects::new(boxes, bins);
+        let (placed, remaining, _) = max_rects.place();
+
+        assert!(remaining.is_empty());
+        let rotated: Vec<bool> = placed.iter().map(|b| b.rotated).collect();
+        let grain_box = placed.iter().position(|b| b.rotated).unwrap();
+        assert_eq!(rotated.iter().filter(|&&r| r).count(), 1);
+        assert_eq!(
+            placed[grain_box].orientation,
+            Orientation::Grain(GrainAxis::Vertical)
+        );
+    }
+
+    #[test]
+    fn test_insert() {
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(vec![], bins);
+
+        let first = max_rects.insert(PackingBox::new(5, 6)).unwrap();
+        assert_eq!((first.originx, first.originy, first.bucketid), (0, 14, 1));
+        assert_eq!(max_rects.bins.len(), 2);
+
+        let second = max_rects.insert(PackingBox::new(4, 4)).unwrap();
+        assert_eq!((second.originx, second.originy), (5, 16));
+        assert_eq!(max_rects.placed.len(), 2);
+        assert_eq!(max_rects.bins.len(), 3);
+
+        assert!(max_rects.insert(PackingBox::new(11, 1)).is_none());
+        assert_eq!(max_rects.placed.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_matches_place() {
+        let boxes = vec![PackingBox::new(5, 6), PackingBox::new(4, 4)];
+        let bins = vec![Bucket::new(10, 20, 0, 0, 1)];
+
+        let mut batch = MaxRects::new(boxes.clone(), bins.clone());
+        let (placed, _, free) = batch.place();
+
+        let mut incremental = MaxRects::new(vec![], bins);
+        for box_item in boxes {
+            incremental.insert(box_item);
+        }
+        let coords: Vec<_> = incremental.placed.iter().map(|b| b.get_coords()).collect();
+        assert_eq!(
+            coords,
+            placed.iter().map(|b| b.get_coords()).collect::<Vec<_>>()
+        );
+        assert_eq!(incremental.bins.len(), free.len());
+    }
+
+    #[test]
+    fn test_place_after_insert() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(vec![PackingBox::new(5, 5)], bins);
+        max_rects.insert(PackingBox::new(5, 5));
+        let (placed, remaining, _) = max_rects.place();
+
+        assert_eq!(placed.len(), 2);
+        assert!(remaining.is_empty());
+        assert_eq!(placed[0].get_coords(), (0, 5, 5, 10));
+        assert_ne!(placed[1].get_coords(), placed[0].get_coords());
+    }
+
+    #[test]
+    fn test_remove_merges_free_space() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(vec![], bins);
+        let placements: Vec<Placement> = (0..4)
+            .map(|_| max_rects.insert(PackingBox::new(5, 5)).unwrap())
+            .collect();
+        assert!(max_rects.bins.is_empty());
+
+        let left: Vec<&Placement> = placements.iter().filter(|p| p.originx == 0).collect();
+        for placement in &left {
+            let removed = max_rects.remove(placement).unwrap();
+            assert_eq!((removed.width, removed.height), (5, 5));
+        }
+
+        assert_eq!(max_rects.placed.len(), 2);
+        assert_eq!(max_rects.bins.len(), 1);
+        assert_eq!(max_rects.bins[0].get_coords(), (0, 5, 0, 10));
+        assert!(max_rects.insert(PackingBox::new(5, 10)).is_some());
+    }
+
+    #[test]
+    fn test_remove_unknown_placement() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(vec![], bins);
+        let placement = max_rects.insert(PackingBox::new(5, 5)).unwrap();
+
+        let elsewhere = Placement {
+            bucketid: 2,
+            ..placement
+        };
+        assert!(max_rects.remove(&elsewhere).is_none());
+        assert!(max_rects.remove(&placement).is_some());
+        assert!(max_rects.remove(&placement).is_none());
+        assert_eq!(max_rects.bins[0].get_coords(), (0, 10, 0, 10));
+    }
+
+    #[test]
+    fn test_bin_by_bin_leaves_trailing_bins_empty() {
+        let boxes = vec![
+            PackingBox::new(6, 6),
+            PackingBox::new(4, 10),
+            PackingBox::new(6, 4),
+            PackingBox::new(8, 8),
+        ];
+        let bins = vec![
+            Bucket::new(10, 10, 0, 0, 0),
+            Bucket::new(10, 10, 0, 0, 1),
+            Bucket::new(10, 10, 0, 0, 2),
+        ];
+        let mut max_rects = MaxRects::new(boxes, bins).with_search(SearchMode::BinByBin);
+        let (placed, remaining, free) = max_rects.place();
+
+        assert!(remaining.is_empty());
+        let in_bin = |id| placed.iter().filter(|b| b.bucketid == Some(id)).count();
+        assert_eq!((in_bin(0), in_bin(1), in_bin(2)), (3, 1, 0));
+        assert!(free
+            .iter()
+            .any(|bin| bin.bucketid == 2 && bin.get_coords() == (0, 10, 0, 10)));
+    }
+
+    #[test]
+    fn test_bin_by_bin_follows_supplied_order() {
+        let boxes = vec![PackingBox::new(5, 5)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 4), Bucket::new(5, 5, 0, 0, 1)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_search(SearchMode::BinByBin);
+        let (placed, _, _) = max_rects.place();
+
+        assert_eq!(placed[0].bucketid, Some(4));
+    }
+
+    #[test]
+    fn test_in_order_follows_box_order() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        let small_first = vec![PackingBox::new(5, 5), PackingBox::new(10, 10)];
+        let mut max_rects =
+            MaxRects::new(small_first, bins.clone()).with_search(SearchMode::InOrder);
+        let (placed, remaining, _) = max_rects.place();
+        assert_eq!(placed[0].width, 5);
+        assert_eq!(remaining[0].width, 10);
+
+        let large_first = vec![PackingBox::new(10, 10), PackingBox::new(5, 5)];
+        let mut max_rects = MaxRects::new(large_first, bins).with_search(SearchMode::InOrder);
+        let (placed, remaining, _) = max_rects.place();
+        assert_eq!(placed[0].width, 10);
+        assert_eq!(remaining[0].width, 5);
+    }
+
+    #[test]
+    fn test_pack_records_sort() {
+        let boxes = vec![PackingBox::new(5, 5), PackingBox::new(10, 10)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        let sort = SortStrategy::descending(SortKey::Area);
+        let packing = MaxRects::new(boxes.clone(), bins.clone())
+            .with_search(SearchMode::InOrder)
+            .with_sort(sort.clone())
+            .pack();
+        assert_eq!(packing.sort, Some(sort));
+        assert_eq!(packing.placed[0].width, 10);
+        assert_eq!(packing.unplaced[0].width, 5);
+
+        let packing = MaxRects::new(boxes, bins).pack();
+        assert!(packing.sort.is_none());
+    }
+
+    #[test]
+    fn test_add_bin() {
+        let mut max_rects = MaxRects::new(vec![PackingBox::new(5, 5)], vec![]);
+        let (placed, _, _) = max_rects.place();
+        assert!(placed.is_empty());
+
+        max_rects.add_bin(Bucket::new(10, 10, 0, 0, 7));
+        let (placed, remaining, _) = max_rects.place();
+        assert_eq!(placed[0].bucketid, Some(7));
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn test_defragment() {
+        // A bin supplied as four quarters cannot take a box larger than a quarter.
+        let bins = vec![
+            Bucket::new(5, 5, 0, 0, 1),
+            Bucket::new(5, 5, 5, 0, 1),
+            Bucket::new(5, 5, 0, 5, 1),
+            Bucket::new(5, 5, 5, 5, 1),
+        ];
+        let mut max_rects = MaxRects::new(vec![], bins);
+        assert!(max_rects.clone().insert(PackingBox::new(8, 8)).is_none());
+
+        assert_eq!(max_rects.defragment(), 3);
+        assert_eq!(max_rects.bins.len(), 1);
+        assert!(max_rects.insert(PackingBox::new(8, 8)).is_some());
+    }
+
+    #[test]
+    fn test_renew_keeps_settings() {
+        let template = MaxRects::new(vec![], vec![]).with_rotation(true);
+        let mut max_rects = template.renew(
+            vec![PackingBox::new(20, 10)],
+            vec![Bucket::new(10, 20, 0, 0, 1)],
+        );
+
+        assert!(max_rects.allow_rotation);
+        assert_eq!(max_rects.containers.len(), 1);
+        let (placed, _, _) = max_rects.place();
+        assert!(placed[0].rotated);
+    }
+
+    /// Returns the smallest gap between any two of `boxes`, measured along whichever axis
+    /// separates them.
+    fn smallest_gap(boxes: &[PackingBox]) -> i32 {
+        let mut smallest = i32::MAX;
+        for (i, a) in boxes.iter().enumerate() {
+            for b in &boxes[i + 1..] {
+                let (ax1, ax2, ay1, ay2) = a.get_coords();
+                let (bx1, bx2, by1, by2) = b.get_coords();
+                let gap = i32::max(
+                    i32::max(bx1 - ax2, ax1 - bx2),
+                    i32::max(by1 - ay2, ay1 - by2),
+                );
+                smallest = smallest.min(gap);
+            }
+        }
+        smallest
+    }
+
+    #[test]
+    fn test_spacing() {
+        let boxes = vec![PackingBox::new(4, 4); 5];
+        let mut max_rects =
+            MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)]).with_spacing(2);
+        let (placed, unplaced, _) = max_rects.place();
+
+        assert_eq!((placed.len(), unplaced.len()), (4, 1));
+        assert_eq!(smallest_gap(&placed), 2);
+        assert!(placed.iter().all(|b| (b.width, b.height) == (4, 4)));
+    }
+
+    #[test]
+    fn test_margin() {
+        let boxes = vec![PackingBox::new(4, 8), PackingBox::new(4, 8)];
+        let mut max_rects = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)]).with_margin(1);
+        let (placed, unplaced, free) = max_rects.place();
+
+        assert_eq!((placed.len(), unplaced.len()), (2, 0));
+        for b in &placed {
+            let (x1, x2, y1, y2) = b.get_coords();
+            assert!(x1 >= 1 && x2 <= 9 && y1 >= 1 && y2 <= 9);
+        }
+        assert!(free.iter().all(|f| f.originx >= 1 && f.originy >= 1));
+
+        // Freed space is rebuilt inside the margin too.
+        max_rects.remove(&placed[0].placement().unwrap());
+        assert!(max_rects.insert(PackingBox::new(5, 8)).is_none());
+        assert!(max_rects.insert(PackingBox::new(4, 8)).is_some());
+    }
+
+    #[test]
+    fn test_padding_overrides_spacing() {
+        let wide = PackingBox::new(4, 10).with_padding(3);
+        let plain = PackingBox::new(4, 10);
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+
+        let mut both = MaxRects::new(vec![plain.clone(), plain.clone()], bins.clone());
+        assert_eq!(both.place().0.len(), 2);
+
+        // The padded box keeps its gap whether it is placed first or second.
+        for boxes in [vec![wide.clone(), plain.clone()], vec![plain, wide]] {
+            let mut max_rects = MaxRects::new(boxes, bins.clone()).with_search(SearchMode::InOrder);
+            let (placed, unplaced, _) = max_rects.place();
+            assert_eq!((placed.len(), unplaced.len()), (1, 1));
+        }
+    }
+
+    #[test]
+    fn test_renew_keeps_margin() {
+        let template = MaxRects::new(vec![], vec![]).with_margin(2);
+        let max_rects = template.renew(vec![], vec![Bucket::new(10, 10, 0, 0, 0)]);
+
+        assert_eq!(max_rects.bins.len(), 1);
+        assert_eq!(max_rects.bins[0].get_coords(), (2, 8, 2, 8));
+    }
+
+    #[test]
+    fn test_kerf_only_between_boxes() {
+        let boxes = vec![PackingBox::new(5, 5); 4];
+        let bins = vec![Bucket::new(11, 11, 0, 0, 0)];
+        let mut max_rects = MaxRects::new(boxes.clone(), bins.clone()).with_kerf(1);
+        let (placed, unplaced, _) = max_rects.place();
+
+        // Padding on every side would need 13 by 13.
+        assert_eq!((placed.len(), unplaced.len()), (4, 0));
+        assert_eq!(smallest_gap(&placed), 1);
+
+        let mut strips = max_rects.cuts();
+        strips.sort_by_key(|s| s.get_coords());
+        let strips: Vec<_> = strips.iter().map(|s| s.get_coords()).collect();
+        assert_eq!(
+            strips,
+            vec![(0, 5, 5, 6), (5, 6, 0, 5), (5, 6, 6, 11), (6, 11, 5, 6)]
+        );
+
+        // The kerf comes on top of the spacing.
+        let mut max_rects = MaxRects::new(boxes, bins).with_kerf(1).with_spacing(1);
+        let (placed, _, _) = max_rects.place();
+        assert_eq!(placed.len(), 1);
+    }
+
+    #[test]
+    fn test_alignment() {
+        let boxes = vec![PackingBox::new(3, 3); 17];
+        let bins = vec![Bucket::new(16, 16, 2, 2, 0)];
+        let mut max_rects = MaxRects::new(boxes, bins).with_alignment(4);
+        let (placed, unplaced, _) = max_rects.place();
+
+        assert_eq!((placed.len(), unplaced.len()), (16, 1));
+        for b in &placed {
+            assert_eq!((b.originx.unwrap() - 2) % 4, 0);
+            assert_eq!((b.originy.unwrap() - 2) % 4, 0);
+            assert_eq!((b.width, b.height), (3, 3));
+        }
+    }
+
+    #[test]
+    fn test_alignment_with_shared_bucketid() {
+        // Two outlines share a bucketid; each box is aligned to the outline it lands in.
+        let bins = vec![Bucket::new(5, 5, 0, 0, 1), Bucket::new(5, 5, 6, 0, 1)];
+        let boxes = vec![PackingBox::new(5, 5); 2];
+        let mut max_rects = MaxRects::new(boxes, bins).with_alignment(4);
+        let (placed, unplaced, _) = max_rects.place();
+
+        assert_eq!((placed.len(), unplaced.len()), (2, 0));
+        let mut xs: Vec<i32> = placed.iter().map(|b| b.originx.unwrap()).collect();
+        xs.sort();
+        assert_eq!(xs, vec![0, 6]);
+    }
+
+    #[test]
+    fn test_box_alignment_and_round_sizes() {
+        let aligned = PackingBox::new(3, 4).with_alignment(4);
+        let boxes = vec![aligned, PackingBox::new(5, 4)];
+        let bins = vec![Bucket::new(8, 4, 0, 0, 0)];
+
+        let template = MaxRects::new(vec![], vec![]).with_search(SearchMode::InOrder);
+        let (placed, _, _) = template.renew(boxes.clone(), bins.clone()).place();
+        assert_eq!(placed.len(), 2);
+        assert_eq!(placed[1].originx, Some(3));
+
+        // Rounded up, the first box takes 4 wide and leaves no room for the second.
+        let template = template.with_round_sizes(true);
+        let (placed, unplaced, _) = template.renew(boxes, bins).place();
+        assert_eq!((placed.len(), unplaced.len()), (1, 1));
+        assert_eq!(placed[0].width, 3);
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!((round_up(5, 4), round_up(8, 4), round_up(-3, 4)), (8, 8, 0));
+        assert_eq!(
+            (round_down(5, 4), round_down(8, 4), round_down(-3, 4)),
+            (4, 8, -4)
+        );
+    }
+
+    #[test]
+    fn test_obstacles() {
+        let sheet = Bucket::new(10, 10, 0, 0, 0).with_obstacle(2, 2, 4, 4);
+        let obstacle = sheet.obstacles[0].clone();
+
+        let mut max_rects = MaxRects::new(vec![PackingBox::new(5, 5)], vec![sheet.clone()]);
+        assert!(max_rects.place().0.is_empty());
+
+        let mut max_rects = MaxRects::new(vec![PackingBox::new(4, 4); 5], vec![sheet.clone()]);
+        let (placed, unplaced, free) = max_rects.place();
+        assert_eq!((placed.len(), unplaced.len()), (4, 1));
+        assert!(placed.iter().all(|b| !b.overlap(&obstacle)));
+        assert!(free.iter().all(|f| !f.overlap(&obstacle)));
+
+        // The obstacle stays out of freed space and moves with the bin to a new id.
+        max_rects.remove(&placed[0].placement().unwrap());
+        let bin = Bucket {
+            bucketid: 7,
+            ..sheet
+        };
+        max_rects.add_bin(bin);
+        assert!(max_rects.insert(PackingBox::new(5, 5)).is_none());
+    }
+
+    #[test]
+    fn test_allowed_bins() {
+        let boxes = vec![
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5).with_allowed_bins([2]),
+        ];
+        let bins = vec![Bucket::new(5, 5, 0, 0, 0), Bucket::new(5, 5, 0, 0, 1)];
+
+        for search in [
+            SearchMode::Global,
+            SearchMode::BinByBin,
+            SearchMode::InOrder,
+        ] {
+            let mut max_rects = MaxRects::new(boxes.clone(), bins.clone()).with_search(search);
+            let (placed, unplaced, _) = max_rects.place();
+
+            assert_eq!((placed.len(), unplaced.len()), (2, 2));
+            assert!(placed.iter().all(|b| b.allows(b.bucketid.unwrap())));
+            assert!(placed
+                .iter()
+                .any(|b| b.allowed_bins.is_none() && b.bucketid == Some(0)));
+            assert!(unplaced.iter().any(|b| b.allows(2) && !b.allows(1)));
+        }
+    }
+
+    #[test]
+    fn test_max_value() {
+        let boxes = vec![
+            PackingBox::new(6, 6).with_value(1.0),
+            PackingBox::new(9, 9).with_value(1.0),
+            PackingBox::new(3, 6).with_value(1.0),
+            PackingBox::new(10, 5).with_value(100.0),
+            PackingBox::new(2, 2).with_value(1.0),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+
+        // Placed in the given order, the filler takes the space the urgent box needs.
+        let (placed, _, _) = MaxRects::new(boxes.clone(), bins.clone())
+            .with_search(SearchMode::InOrder)
+            .place();
+        assert!(placed.iter().all(|b| b.value() < 100.0));
+
+        let (placed, unplaced, _) = MaxRects::new(boxes, bins)
+            .with_search(SearchMode::MaxValue)
+            .place();
+        let sizes = |boxes: &[PackingBox]| -> Vec<(i32, i32)> {
+            boxes.iter().map(|b| (b.width, b.height)).collect()
+        };
+        assert_eq!(sizes(&placed), vec![(10, 5), (2, 2)]);
+        // The 3x6 and 6x6 boxes come equally close, so the denser one stays first.
+        assert_eq!(sizes(&unplaced), vec![(3, 6), (6, 6), (9, 9)]);
+    }
+
+    #[test]
+    fn test_max_value_beats_greedy_by_value() {
+        let boxes = vec![
+            PackingBox::new(10, 10).with_value(10.0),
+            PackingBox::new(5, 10).with_value(6.0),
+            PackingBox::new(5, 10).with_value(6.0),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0)];
+        let value = |placed: &[PackingBox]| placed.iter().map(|b| b.value()).sum::<f64>();
+
+        // Most valuable first, the large box fills the bin on its own.
+        let (placed, _, _) = MaxRects::new(boxes.clone(), bins.clone())
+            .with_search(SearchMode::InOrder)
+            .place();
+        assert_eq!(value(&placed), 10.0);
+
+        let (placed, unplaced, _) = MaxRects::new(boxes, bins)
+            .with_search(SearchMode::MaxValue)
+            .place();
+        assert_eq!(value(&placed), 12.0);
+        assert_eq!(unplaced.len(), 1);
+    }
+
+    #[test]
+    fn test_max_value_swaps_boxes_in() {
+        // Both greedy orders fall short: by value the large box fills the bin, and by value
+        // density the small box blocks the 4x10 one. Swapping the 6x10 box in for the large
+        // one makes room for the 4x10 box.
+        let boxes = vec![
+            PackingBox::new(10, 10).with_value(10.0),
+            PackingBox::new(1, 1).with_value(0.5),
+            PackingBox::new(6, 10).with_value(9.0),
+            PackingBox::new(4, 10).with_value(2.0),
+        ];
+        let mut max_rects = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+            .with_search(SearchMode::MaxValue);
+        let (placed, _, _) = max_rects.place();
+
+        let mut sizes: Vec<(i32, i32)> = placed.iter().map(|b| (b.width, b.height)).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![(4, 10), (6, 10)]);
+    }
+
+    #[test]
+    fn test_nearest_fit_uses_bin_grain() {
+        let bins = vec![Bucket::new(10, 4, 0, 0, 0).with_grain(GrainAxis::Horizontal)];
+        let board =
+            PackingBox::new(10, 3).with_orientation(Orientation::Grain(GrainAxis::Vertical));
+        let mut max_rects = MaxRects::new(vec![board.clone()], bins)
+            .with_rotation(true)
+            .with_search(SearchMode::MaxValue);
+        let (placed, _, _) = max_rects.place();
+
+        // Only the turned, 3 by 10 orientation follows the grain.
+        assert!(placed.is_empty());
+        assert_eq!(max_rects.nearest_fit(&board), 0.4);
+    }
+
+    #[test]
+    fn test_max_value_orders_dropped_groups() {
+        let boxes = vec![
+            PackingBox::new(10, 4).with_value(5.0),
+            PackingBox::new(5, 7).with_value(2.0),
+            PackingBox::new(10, 6).with_value(1.0).with_group("g"),
+            PackingBox::new(10, 6).with_value(1.0).with_group("g"),
+        ];
+        let mut max_rects = MaxRects::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+            .with_search(SearchMode::MaxValue);
+        let (placed, unplaced, _) = max_rects.place();
+
+        assert_eq!(placed.len(), 1);
+        let sizes: Vec<(i32, i32)> = unplaced.iter().map(|b| (b.width, b.height)).collect();
+        assert_eq!(sizes, vec![(10, 6), (10, 6), (5, 7)]);
+    }
+
+    #[test]
+    fn test_groups_share_a_bin() {
+        let mut boxes = vec![PackingBox::new(5, 5).with_group("a"); 3];
+        boxes.extend(vec![PackingBox::new(5, 5).with_group("b"); 2]);
+        boxes.push(PackingBox::new(5, 5));
+        let bins = vec![
+            Bucket::new(10, 5, 0, 0, 0),
+            Bucket::new(10, 10, 0, 0, 1),
+            Bucket::new(5, 5, 0, 0, 2),
+        ];
+
+        for search in [
+            SearchMode::Global,
+            SearchMode::BinByBin,
+            SearchMode::InOrder,
+        ] {
+            let mut max_rects = MaxRects::new(boxes.clone(), bins.clone()).with_search(search);
+            let (placed, unplaced, _) = max_rects.place();
+
+            assert_eq!((placed.len(), unplaced.len()), (6, 0));
+            for group in ["a", "b"] {
+                let mut ids: Vec<_> = placed
+                    .iter()
+                    .filter(|b| b.group.as_deref() == Some(group))
+                    .map(|b| b.bucketid)
+                    .collect();
+                ids.dedup();
+                assert_eq!(ids.len(), 1);
+            }
+            assert!(max_rects.pins.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_group_that_does_not_fit_stays_unplaced() {
+        let mut boxes = vec![PackingBox::new(5, 5).with_group("order"); 3];
+        boxes.push(PackingBox::new(5, 5));
+        let bins = vec![Bucket::new(10, 5, 0, 0, 0), Bucket::new(10, 5, 0, 0, 1)];
+        let (placed, unplaced, _) = MaxRects::new(boxes, bins).place();
+
+        assert_eq!(placed.len(), 1);
+        assert!(placed[0].group.is_none());
+        assert_eq!(unplaced.len(), 3);
+        assert!(unplaced.iter().all(|b| b.group.is_some()));
+    }
+
+    #[test]
+    fn test_insert_joins_group() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)];
+        let mut atlas = MaxRects::new(vec![], bins);
+        let first = atlas
+            .insert(PackingBox::new(5, 5).with_group("ui"))
+            .unwrap();
+
+        for _ in 0..3 {
+            let next = atlas
+                .insert(PackingBox::new(5, 5).with_group("ui"))
+                .unwrap();
+            assert_eq!(next.bucketid, first.bucketid);
+        }
+        // The other bin is empty, but the group's bin is full.
+        assert!(atlas
+            .insert(PackingBox::new(5, 5).with_group("ui"))
+            .is_none());
+        assert!(atlas.insert(PackingBox::new(5, 5)).is_some());
+    }
+
     #[test]
     fn test_place_no_fit() {
         let boxes = vec![PackingBox::new(15, 16)]; // Box is too big to fit in the bin.
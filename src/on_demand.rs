@@ -0,0 +1,239 @@
+//! The `on_demand` module packs boxes into bins that are opened only when needed, chosen
+//! from a list of bin templates. It answers "how many sheets do I need" rather than "pack
+//! into these sheets".
+
+use crate::bucket::{Bucket, GrainAxis};
+use crate::max_rects::MaxRects;
+use crate::packing_box::PackingBox;
+
+/// A kind of bin that can be opened on demand.
+///
+/// - `bin`: The size, origin and grain of every bin opened from this template. Its
+///   `bucketid` is ignored.
+/// - `quantity`: How many bins of this kind may be opened, or `None` for no limit.
+/// - `cost`: The cost of opening one bin of this kind. Defaults to the bin's area.
+#[derive(Debug, Clone)]
+pub struct BinTemplate {
+    pub bin: Bucket,
+    pub quantity: Option<usize>,
+    pub cost: f64,
+}
+
+impl BinTemplate {
+    /// Creates an unlimited template for `width` by `height` bins costing their area.
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            bin: Bucket::new(width, height, 0, 0, 0),
+            quantity: None,
+            cost: width as f64 * height as f64,
+        }
+    }
+    /// Limits how many bins may be opened from this template.
+    pub fn with_quantity(mut self, quantity: usize) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+    /// Sets the cost of opening one bin from this template.
+    pub fn with_cost(mut self, cost: f64) -> Self {
+        self.cost = cost;
+        self
+    }
+    /// Sets the grain direction of bins opened from this template.
+    pub fn with_grain(mut self, grain: GrainAxis) -> Self {
+        self.bin.grain = Some(grain);
+        self
+    }
+}
+
+/// An `OnDemand` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `templates`: The kinds of bin that may be opened.
+/// - `packer`: The template used to fill the bins, see [`MaxRects::renew`].
+/// - `opened`: The bins opened by `place`, each with the index of its template.
+#[derive(Clone)]
+pub struct OnDemand {
+    pub boxes: Vec<PackingBox>,
+    pub templates: Vec<BinTemplate>,
+    pub packer: MaxRects,
+    pub opened: Vec<(usize, Bucket)>,
+}
+
+impl OnDemand {
+    /// Constructs a new `OnDemand` instance.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `templates`: The kinds of bin that may be opened.
+    ///
+    /// # Returns
+    /// A new `OnDemand` object.
+    pub fn new(boxes: Vec<PackingBox>, templates: Vec<BinTemplate>) -> Self {
+        Self {
+            boxes,
+            templates,
+            packer: MaxRects::new(vec![], vec![]),
+            opened: vec![],
+        }
+    }
+    /// Sets the template used to fill the bins.
+    pub fn with_packer(mut self, packer: MaxRects) -> Self {
+        self.packer = packer;
+        self
+    }
+    /// Returns how many bins were opened from each template, in template order.
+    pub fn usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; self.templates.len()];
+        for (template, _) in &self.opened {
+            usage[*template] += 1;
+        }
+        usage
+    }
+    /// Returns the total cost of the bins opened.
+    pub fn total_cost(&self) -> f64 {
+        self.opened
+            .iter()
+            .map(|(template, _)| self.templates[*template].cost)
+            .sum()
+    }
+    /// Packs the boxes, opening a new bin whenever no remaining box fits in the bins already
+    /// open.
+    ///
+    /// Each new bin comes from the template, with quantity left, that packs the most box area
+    /// per unit of cost from the boxes still waiting. Bins are numbered from 0 in the order
+    /// they are opened. Boxes that fit no template are returned unplaced.
+    ///
+    /// # Returns
+    /// A tuple of three vectors:
+    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
+    /// - A vector of `Bucket` objects representing the free space left in the opened bins.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{on_demand::{BinTemplate, OnDemand}, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(50, 50); 10];
+    /// let mut sheets = OnDemand::new(boxes, vec![BinTemplate::new(100, 100)]);
+    /// let (placed, unplaced, _) = sheets.place();
+    ///
+    /// assert_eq!(placed.len(), 10);
+    /// assert!(unplaced.is_empty());
+    /// assert_eq!(sheets.usage(), vec![3]);
+    /// ```
+    pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        self.opened.clear();
+        let mut live = self.packer.renew(self.boxes.clone(), vec![]);
+
+        loop {
+            live.place();
+            if live.boxes.is_empty() {
+                break;
+            }
+
+            let usage = self.usage();
+            let bucketid = self.opened.len() as i32;
+            let mut best: Option<(f64, usize, Bucket)> = None;
+
+            for (t, template) in self.templates.iter().enumerate() {
+                if template
+                    .quantity
+                    .is_some_and(|quantity| usage[t] >= quantity)
+                {
+                    continue;
+                }
+                let bin = Bucket {
+                    bucketid,
+                    ..template.bin.clone()
+                };
+                let (placed, _, _) = live.renew(live.boxes.clone(), vec![bin.clone()]).place();
+                let area: i64 = placed
+                    .iter()
+                    .map(|b| b.width as i64 * b.height as i64)
+                    .sum();
+                if area == 0 {
+                    continue;
+                }
+                let value = area as f64 / template.cost;
+                if best.as_ref().is_none_or(|(v, _, _)| value > *v) {
+                    best = Some((value, t, bin));
+                }
+            }
+
+            match best {
+                Some((_, t, bin)) => {
+                    live.add_bin(bin.clone());
+                    self.opened.push((t, bin));
+                }
+                None => break,
+            }
+        }
+
+        (live.placed, live.boxes, live.bins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_only_needed_bins() {
+        let boxes = vec![PackingBox::new(50, 50); 5];
+        let mut on_demand = OnDemand::new(boxes, vec![BinTemplate::new(100, 100)]);
+        let (placed, unplaced, _) = on_demand.place();
+
+        assert_eq!(placed.len(), 5);
+        assert!(unplaced.is_empty());
+        assert_eq!(on_demand.usage(), vec![2]);
+        assert_eq!(on_demand.opened[1].1.bucketid, 1);
+        assert_eq!(placed.iter().filter(|b| b.bucketid == Some(0)).count(), 4);
+    }
+
+    #[test]
+    fn test_large_template_cost() {
+        let template = BinTemplate::new(100_000, 100_000);
+
+        assert_eq!(template.cost, 1e10);
+    }
+
+    #[test]
+    fn test_prefers_cheaper_template() {
+        let boxes = vec![PackingBox::new(50, 50); 5];
+        let templates = vec![
+            BinTemplate::new(100, 100).with_cost(10000.0),
+            BinTemplate::new(50, 50).with_cost(2000.0),
+        ];
+        let mut on_demand = OnDemand::new(boxes, templates);
+        on_demand.place();
+
+        assert_eq!(on_demand.usage(), vec![0, 5]);
+        assert_eq!(on_demand.total_cost(), 10000.0);
+    }
+
+    #[test]
+    fn test_respects_quantity() {
+        let boxes = vec![PackingBox::new(50, 50); 5];
+        let templates = vec![
+            BinTemplate::new(100, 100).with_cost(10000.0),
+            BinTemplate::new(50, 50).with_cost(2000.0).with_quantity(2),
+        ];
+        let mut on_demand = OnDemand::new(boxes, templates);
+        let (placed, _, _) = on_demand.place();
+
+        assert_eq!(placed.len(), 5);
+        assert_eq!(on_demand.usage(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_unplaceable_boxes() {
+        let boxes = vec![PackingBox::new(200, 10), PackingBox::new(10, 10)];
+        let templates = vec![BinTemplate::new(100, 100).with_quantity(1)];
+        let mut on_demand = OnDemand::new(boxes, templates);
+        let (placed, unplaced, free) = on_demand.place();
+
+        assert_eq!(placed.len(), 1);
+        assert_eq!(unplaced[0].width, 200);
+        assert_eq!(free.len(), 2);
+        assert_eq!(on_demand.usage(), vec![1]);
+    }
+}
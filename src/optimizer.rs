@@ -0,0 +1,423 @@
+//! The `optimizer` module improves on a single greedy packing by simulated annealing over the
+//! order in which boxes are handed to `MaxRects` and the orientation each box is placed in.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+use crate::bounds::{Gap, LowerBounds};
+use crate::bucket::Bucket;
+use crate::max_rects::{MaxRects, SearchMode};
+use crate::packing_box::{Orientation, PackingBox};
+use crate::portfolio::{bins_used, Objective};
+use crate::sort::SortStrategy;
+
+/// How long the optimizer runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// A fixed number of perturbations. Runs with the same seed give the same result.
+    Iterations(usize),
+    /// A wall-clock limit. How many perturbations fit depends on the machine, so results are
+    /// only reproducible for the same number of iterations.
+    Time(Duration),
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::Iterations(1000)
+    }
+}
+
+/// A point on the improvement curve, recorded whenever a new best packing is found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Improvement {
+    pub iteration: usize,
+    pub elapsed: Duration,
+    pub score: (usize, f64),
+}
+
+/// An `OptimizerResult` object contains the following fields:
+/// - `placed`: The placed boxes of the best packing found.
+/// - `unplaced`: The boxes that packing left unplaced.
+/// - `free`: The free space left in the bins.
+/// - `order`: The indices into the input boxes, in the order they were handed to the packer.
+/// - `sort`: The pre-sort the search started from.
+/// - `curve`: Every improvement of the best packing, starting with the initial packing.
+/// - `iterations`: The number of perturbations tried.
+/// - `gap`: How far the number of bins used is from the lower bound, if the bins all have
+///   the same size.
+#[derive(Debug, Clone)]
+pub struct OptimizerResult {
+    pub placed: Vec<PackingBox>,
+    pub unplaced: Vec<PackingBox>,
+    pub free: Vec<Bucket>,
+    pub order: Vec<usize>,
+    pub sort: SortStrategy,
+    pub curve: Vec<Improvement>,
+    pub iterations: usize,
+    pub gap: Option<Gap>,
+}
+
+/// An `Optimizer` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `objective`: How packings are compared.
+/// - `budget`: How long to search.
+/// - `seed`: The seed of the random number generator.
+/// - `sort`: The pre-sort giving the order the search starts from.
+/// - `packer`: The template for every packing, see [`MaxRects::renew`]. Its search mode and
+///   pre-sort are overridden, as the search chooses the order itself.
+#[derive(Clone)]
+pub struct Optimizer {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub objective: Objective,
+    pub budget: Budget,
+    pub seed: u64,
+    pub sort: SortStrategy,
+    pub packer: MaxRects,
+}
+
+/// A candidate solution: the order of the boxes and which boxes are turned before packing.
+#[derive(Clone)]
+struct State {
+    order: Vec<usize>,
+    turned: Vec<bool>,
+}
+
+impl Optimizer {
+    /// Constructs a new `Optimizer` that minimizes the number of bins used within 1000
+    /// iterations, seeded with 0 and starting from the boxes sorted by decreasing area.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Optimizer` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        Self {
+            boxes,
+            bins,
+            objective: Objective::default(),
+            budget: Budget::default(),
+            seed: 0,
+            sort: SortStrategy::default(),
+            packer: MaxRects::new(vec![], vec![]),
+        }
+    }
+    /// Sets how packings are compared.
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+    /// Sets how long to search.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+    /// Sets the seed of the random number generator.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    /// Sets the pre-sort giving the order the search starts from.
+    pub fn with_sort(mut self, sort: SortStrategy) -> Self {
+        self.sort = sort;
+        self
+    }
+    /// Sets the template for every packing.
+    pub fn with_packer(mut self, packer: MaxRects) -> Self {
+        self.packer = packer;
+        self
+    }
+    /// Whether the optimizer chooses the orientation of the box at `idx` itself.
+    fn flippable(&self, idx: usize) -> bool {
+        let box_item = &self.boxes[idx];
+        self.packer.allow_rotation
+            && box_item.orientation != Orientation::Fixed
+            && box_item.width != box_item.height
+    }
+    /// Packs the boxes in the order and orientations of `state`.
+    ///
+    /// The packer itself is not allowed to turn boxes, so each box keeps the orientation
+    /// chosen by `state`, except that grain still turns boxes to match their bin.
+    fn pack(&self, state: &State) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        let boxes = state
+            .order
+            .iter()
+            .map(|&idx| {
+                let mut box_item = self.boxes[idx].clone();
+                if state.turned[idx] {
+                    box_item.rotate();
+                }
+                box_item
+            })
+            .collect();
+        let mut packer = self
+            .packer
+            .renew(boxes, self.bins.clone())
+            .with_rotation(false)
+            .with_search(SearchMode::InOrder);
+        packer.sort = None;
+        packer.place()
+    }
+    /// Turns a score into a single number for the acceptance test, weighting each unplaced
+    /// box above any possible difference in the objective.
+    fn energy(&self, score: (usize, f64)) -> f64 {
+        let weight =
+            self.bins.iter().map(|b| b.area() as f64).sum::<f64>() + self.bins.len() as f64 + 101.0;
+        score.0 as f64 * weight + score.1
+    }
+    /// Returns a copy of `state` with two boxes swapped or one box turned.
+    fn perturb(&self, state: &State, rng: &mut StdRng) -> State {
+        let mut next = state.clone();
+        let n = next.order.len();
+        let flippable: Vec<usize> = (0..n).filter(|&idx| self.flippable(idx)).collect();
+
+        if !flippable.is_empty() && (n < 2 || rng.gen_bool(0.3)) {
+            let idx = flippable[rng.gen_range(0..flippable.len())];
+            next.turned[idx] = !next.turned[idx];
+        } else if n >= 2 {
+            let a = rng.gen_range(0..n);
+            let b = (a + rng.gen_range(1..n)) % n;
+            next.order.swap(a, b);
+        }
+        next
+    }
+    /// Searches for a better packing by simulated annealing.
+    ///
+    /// The search starts from the boxes sorted by `sort`, packed in order. Each
+    /// iteration swaps two boxes in the order or, when rotation is allowed, turns one box,
+    /// and packs again. Better packings are always kept and worse ones are kept with a
+    /// probability that falls as the budget runs out.
+    ///
+    /// # Returns
+    /// The best packing found, the order that produced it and the improvement curve.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, optimizer::{Budget, Optimizer}, packing_box::PackingBox};
+    ///
+    /// let boxes = vec![PackingBox::new(6, 4), PackingBox::new(4, 6), PackingBox::new(4, 4)];
+    /// let result = Optimizer::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+    ///     .with_budget(Budget::Iterations(50))
+    ///     .with_seed(7)
+    ///     .run();
+    ///
+    /// assert!(result.unplaced.is_empty());
+    /// assert_eq!(result.curve[0].iteration, 0);
+    /// ```
+    pub fn run(&self) -> OptimizerResult {
+        let start = Instant::now();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let mut order: Vec<usize> = (0..self.boxes.len()).collect();
+        order.sort_by(|&a, &b| self.sort.compare(&self.boxes[a], &self.boxes[b]));
+        let mut current = State {
+            order,
+            turned: vec![false; self.boxes.len()],
+        };
+
+        let mut packing = self.pack(&current);
+        let mut current_score = self.objective.score(&packing.0, &packing.1, &self.bins);
+        let mut best = (current.clone(), packing.clone(), current_score);
+        let mut curve = vec![Improvement {
+            iteration: 0,
+            elapsed: start.elapsed(),
+            score: current_score,
+        }];
+
+        let initial_temperature = f64::max(self.energy(current_score).abs() * 0.05, 1e-9);
+        let mut iterations = 0;
+
+        loop {
+            let progress = match self.budget {
+                Budget::Iterations(limit) => iterations as f64 / limit.max(1) as f64,
+                Budget::Time(limit) => {
+                    start.elapsed().as_secs_f64() / limit.as_secs_f64().max(1e-9)
+                }
+            };
+            if progress >= 1.0 {
+                break;
+            }
+            iterations += 1;
+            let temperature = initial_temperature * 0.001f64.powf(progress);
+
+            let candidate = self.perturb(&current, &mut rng);
+            let candidate_packing = self.pack(&candidate);
+            let score =
+                self.objective
+                    .score(&candidate_packing.0, &candidate_packing.1, &self.bins);
+            let delta = self.energy(score) - self.energy(current_score);
+
+            if delta <= 0.0 || rng.gen_bool(f64::exp(-delta / temperature)) {
+                current = candidate;
+                packing = candidate_packing;
+                current_score = score;
+
+                if self.energy(current_score) < self.energy(best.2) {
+                    best = (current.clone(), packing.clone(), current_score);
+                    curve.push(Improvement {
+                        iteration: iterations,
+                        elapsed: start.elapsed(),
+                        score: current_score,
+                    });
+                }
+            }
+        }
+
+        let (state, (placed, unplaced, free), _) = best;
+        let bounds = LowerBounds::for_bins(&self.boxes, &self.bins, self.packer.allow_rotation);
+        OptimizerResult {
+            gap: bounds.map(|b| b.gap(bins_used(&placed))),
+            placed,
+            unplaced,
+            free,
+            order: state.order,
+            sort: self.sort.clone(),
+            curve,
+            iterations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket::GrainAxis;
+    use crate::sort::SortKey;
+
+    fn boxes() -> Vec<PackingBox> {
+        vec![
+            PackingBox::new(7, 3),
+            PackingBox::new(3, 7),
+            PackingBox::new(4, 4),
+            PackingBox::new(6, 2),
+            PackingBox::new(2, 5),
+            PackingBox::new(5, 3),
+            PackingBox::new(3, 3),
+            PackingBox::new(2, 2),
+        ]
+    }
+
+    fn bins() -> Vec<Bucket> {
+        vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)]
+    }
+
+    #[test]
+    fn test_packer_sort_does_not_override_order() {
+        let boxes = vec![
+            PackingBox::new(2, 2),
+            PackingBox::new(5, 5),
+            PackingBox::new(3, 3),
+        ];
+        let packer = MaxRects::new(vec![], vec![]).with_sort(SortStrategy::default());
+        let result = Optimizer::new(boxes.clone(), vec![Bucket::new(20, 20, 0, 0, 0)])
+            .with_packer(packer)
+            .with_sort(SortStrategy::ascending(SortKey::Area))
+            .with_budget(Budget::Iterations(20))
+            .run();
+
+        assert_eq!(result.sort, SortStrategy::ascending(SortKey::Area));
+        let widths: Vec<i32> = result.placed.iter().map(|b| b.width).collect();
+        let ordered: Vec<i32> = result.order.iter().map(|&i| boxes[i].width).collect();
+        assert_eq!(widths, ordered);
+    }
+
+    #[test]
+    fn test_run_is_reproducible() {
+        let optimizer = Optimizer::new(boxes(), bins())
+            .with_packer(MaxRects::new(vec![], vec![]).with_rotation(true))
+            .with_budget(Budget::Iterations(200))
+            .with_seed(3);
+        let first = optimizer.run();
+        let second = optimizer.run();
+
+        assert_eq!(first.order, second.order);
+        assert_eq!(first.iterations, 200);
+        let placements =
+            |r: &OptimizerResult| r.placed.iter().map(|b| b.placement()).collect::<Vec<_>>();
+        assert_eq!(placements(&first), placements(&second));
+    }
+
+    #[test]
+    fn test_curve_only_improves() {
+        let result = Optimizer::new(boxes(), bins())
+            .with_budget(Budget::Iterations(300))
+            .with_seed(11)
+            .run();
+
+        for pair in result.curve.windows(2) {
+            assert!(pair[0].iteration < pair[1].iteration);
+            assert!(pair[1].score.0 < pair[0].score.0 || pair[1].score.1 < pair[0].score.1);
+        }
+        let last = result.curve.last().unwrap().score;
+        let score = Objective::BinsUsed.score(&result.placed, &result.unplaced, &bins());
+        assert_eq!(score, last);
+    }
+
+    #[test]
+    fn test_run_improves_on_greedy() {
+        // Packed largest first, one of the boxes is left over.
+        let boxes = vec![
+            PackingBox::new(8, 2),
+            PackingBox::new(4, 3),
+            PackingBox::new(4, 5),
+            PackingBox::new(1, 1),
+            PackingBox::new(3, 7),
+        ];
+        let result = Optimizer::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+            .with_budget(Budget::Iterations(100))
+            .with_seed(1)
+            .run();
+
+        assert_eq!(result.curve[0].score, (1, 1.0));
+        assert!(result.unplaced.is_empty());
+        assert_eq!(result.curve.last().unwrap().score, (0, 1.0));
+        assert_eq!(result.gap.unwrap().absolute, 0);
+    }
+
+    #[test]
+    fn test_run_turns_boxes() {
+        let boxes = vec![
+            PackingBox::new(10, 4),
+            PackingBox::new(10, 4),
+            PackingBox::new(2, 10),
+        ];
+        let result = Optimizer::new(boxes, vec![Bucket::new(10, 10, 0, 0, 0)])
+            .with_packer(MaxRects::new(vec![], vec![]).with_rotation(true))
+            .with_budget(Budget::Iterations(100))
+            .run();
+
+        assert!(result.unplaced.is_empty());
+    }
+
+    #[test]
+    fn test_run_keeps_grain() {
+        let boxes = vec![
+            PackingBox::new(8, 2)
+                .with_orientation(Orientation::Grain(GrainAxis::Horizontal));
+            3
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0).with_grain(GrainAxis::Vertical)];
+        let result = Optimizer::new(boxes, bins)
+            .with_packer(MaxRects::new(vec![], vec![]).with_rotation(true))
+            .with_budget(Budget::Iterations(30))
+            .run();
+
+        assert_eq!(result.placed.len(), 3);
+        assert!(result.placed.iter().all(|b| b.rotated && b.width == 2));
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let start = Instant::now();
+        let result = Optimizer::new(boxes(), bins())
+            .with_budget(Budget::Time(Duration::from_millis(20)))
+            .run();
+
+        assert!(result.iterations > 0);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::bucket::Bucket;
+use std::collections::HashSet;
+
+use crate::bucket::{Bucket, GrainAxis};
 /// Represents a rectangular box with a defined width and height, which can be placed within a bucket in a 2D space.
 ///
 /// # Examples
@@ -16,6 +18,15 @@ use crate::bucket::Bucket;
 /// assert_eq!(box_item.originy, Some(20));
 /// assert_eq!(box_item.bucketid, Some(1));
 /// ```
+///
+/// `rotated` records whether the box was turned by 90 degrees during packing, in which case
+/// `width` and `height` describe the rotated footprint. `orientation` controls whether the
+/// packer is allowed to turn the box at all. `padding`, when set, replaces the packer's
+/// spacing as the clearance kept around this box, and `alignment` replaces the packer's
+/// alignment of its origin. `allowed_bins`, when set, lists the only `bucketid`s the box
+/// may be placed in. `value`, when set, is what placing the box is worth to value-maximising
+/// packers; it defaults to the box's area. Boxes sharing a `group` are placed in the same bin
+/// or not at all.
 #[derive(Debug, Clone)]
 pub struct PackingBox {
     pub width: i32,
@@ -23,6 +34,36 @@ pub struct PackingBox {
     pub originx: Option<i32>,
     pub originy: Option<i32>,
     pub bucketid: Option<i32>,
+    pub rotated: bool,
+    pub orientation: Orientation,
+    pub padding: Option<i32>,
+    pub alignment: Option<i32>,
+    pub allowed_bins: Option<HashSet<i32>>,
+    pub value: Option<f64>,
+    pub group: Option<String>,
+}
+
+/// Where a box ended up: its origin, the bucket it was placed in and whether it was turned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub originx: i32,
+    pub originy: i32,
+    pub bucketid: i32,
+    pub rotated: bool,
+}
+
+/// How a box may be oriented when it is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    /// The box may be turned whenever the packer allows rotation.
+    #[default]
+    Rotatable,
+    /// The box is never turned.
+    Fixed,
+    /// The box's grain runs along the given axis of its unrotated shape and must line up with
+    /// the grain of the bucket it is placed in, turning the box if needed. In buckets without
+    /// a grain the box behaves as `Rotatable`.
+    Grain(GrainAxis),
 }
 
 impl PackingBox {
@@ -38,6 +79,99 @@ impl PackingBox {
             originx: None,
             originy: None,
             bucketid: None,
+            rotated: false,
+            orientation: Orientation::Rotatable,
+            padding: None,
+            alignment: None,
+            allowed_bins: None,
+            value: None,
+            group: None,
+        }
+    }
+    /// Sets the box's orientation policy.
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+    /// Sets the clearance kept between this box and its neighbours, overriding the packer's
+    /// spacing.
+    pub fn with_padding(mut self, padding: i32) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+    /// Requires the box's origin to lie on multiples of `alignment`, overriding the packer's
+    /// alignment.
+    pub fn with_alignment(mut self, alignment: i32) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+    /// Restricts the box to the bins with the given `bucketid`s.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::packing_box::PackingBox;
+    ///
+    /// let sprite = PackingBox::new(5, 6).with_allowed_bins([2]);
+    /// assert!(sprite.allows(2));
+    /// assert!(!sprite.allows(0));
+    /// ```
+    pub fn with_allowed_bins(mut self, bucketids: impl IntoIterator<Item = i32>) -> Self {
+        self.allowed_bins = Some(bucketids.into_iter().collect());
+        self
+    }
+    /// Sets what placing the box is worth, such as the priority of the order it belongs to.
+    pub fn with_value(mut self, value: f64) -> Self {
+        self.value = Some(value);
+        self
+    }
+    /// Tags the box as a member of `group`, such as the frames of one animation or the
+    /// parts of one order.
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+    /// Returns what placing the box is worth: its `value` if set, otherwise its area.
+    pub fn value(&self) -> f64 {
+        self.value
+            .unwrap_or((self.width as i64 * self.height as i64) as f64)
+    }
+    /// Whether the box may be placed in the bin with `bucketid`.
+    pub fn allows(&self, bucketid: i32) -> bool {
+        self.allowed_bins
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&bucketid))
+    }
+    /// Turns the box by 90 degrees, swapping its width and height and toggling `rotated`.
+    pub fn rotate(&mut self) {
+        std::mem::swap(&mut self.width, &mut self.height);
+        self.rotated = !self.rotated;
+    }
+    /// Returns the orientations the box may be placed in as `(width, height, rotate)` tuples,
+    /// where `rotate` says whether the box has to be turned to take that orientation.
+    ///
+    /// `allow_rotation` is the packer's global rotation switch and `grain` is the grain of the
+    /// bucket being considered. Square boxes only report their current orientation, as
+    /// turning them changes nothing.
+    pub fn orientations(
+        &self,
+        allow_rotation: bool,
+        grain: Option<GrainAxis>,
+    ) -> Vec<(i32, i32, bool)> {
+        let upright = (self.width, self.height, false);
+        let turned = (self.height, self.width, true);
+
+        match (self.orientation, grain) {
+            (Orientation::Fixed, _) => vec![upright],
+            (Orientation::Grain(axis), Some(grain)) => {
+                let current = if self.rotated { axis.turned() } else { axis };
+                if current == grain {
+                    vec![upright]
+                } else {
+                    vec![turned]
+                }
+            }
+            _ if allow_rotation && self.width != self.height => vec![upright, turned],
+            _ => vec![upright],
         }
     }
     /// Places the box at the specified coordinates within a bucket.
@@ -51,6 +185,15 @@ impl PackingBox {
         self.originy = Some(originy);
         self.bucketid = Some(bucketid);
     }
+    /// Returns where the box was placed, or `None` if it has not been placed yet.
+    pub fn placement(&self) -> Option<Placement> {
+        Some(Placement {
+            originx: self.originx?,
+            originy: self.originy?,
+            bucketid: self.bucketid?,
+            rotated: self.rotated,
+        })
+    }
     /// Returns the coordinates of the corners of the box.
     ///
     /// The coordinates are returned as a tuple of four `i32` values: `(left, right, top, bottom)`.
@@ -87,8 +230,7 @@ impl PackingBox {
 
 #[cfg(test)]
 mod tests {
-    use super::Bucket;
-    use super::PackingBox;
+    use super::{Bucket, GrainAxis, Orientation, PackingBox, Placement};
 
     #[test]
     fn test_new() {
@@ -98,6 +240,66 @@ mod tests {
         assert_eq!(box_item.originx, None);
         assert_eq!(box_item.originy, None);
         assert_eq!(box_item.bucketid, None);
+        assert!(!box_item.rotated);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut box_item = PackingBox::new(5, 6);
+        box_item.rotate();
+        assert_eq!((box_item.width, box_item.height), (6, 5));
+        assert!(box_item.rotated);
+        box_item.rotate();
+        assert_eq!((box_item.width, box_item.height), (5, 6));
+        assert!(!box_item.rotated);
+    }
+
+    #[test]
+    fn test_orientations() {
+        let box_item = PackingBox::new(5, 6);
+        assert_eq!(box_item.orientations(false, None), vec![(5, 6, false)]);
+        assert_eq!(
+            box_item.orientations(true, None),
+            vec![(5, 6, false), (6, 5, true)]
+        );
+        assert_eq!(PackingBox::new(4, 4).orientations(true, None).len(), 1);
+    }
+
+    #[test]
+    fn test_orientations_policy() {
+        let fixed = PackingBox::new(5, 6).with_orientation(Orientation::Fixed);
+        assert_eq!(fixed.orientations(true, None), vec![(5, 6, false)]);
+
+        let grain = PackingBox::new(5, 6).with_orientation(Orientation::Grain(GrainAxis::Vertical));
+        assert_eq!(
+            grain.orientations(false, Some(GrainAxis::Vertical)),
+            vec![(5, 6, false)]
+        );
+        assert_eq!(
+            grain.orientations(false, Some(GrainAxis::Horizontal)),
+            vec![(6, 5, true)]
+        );
+        assert_eq!(grain.orientations(false, None), vec![(5, 6, false)]);
+
+        let mut turned = grain.clone();
+        turned.rotate();
+        assert_eq!(
+            turned.orientations(false, Some(GrainAxis::Horizontal)),
+            vec![(6, 5, false)]
+        );
+    }
+
+    #[test]
+    fn test_with_padding() {
+        assert_eq!(PackingBox::new(5, 6).padding, None);
+        assert_eq!(PackingBox::new(5, 6).with_padding(2).padding, Some(2));
+        assert_eq!(PackingBox::new(5, 6).with_alignment(4).alignment, Some(4));
+    }
+
+    #[test]
+    fn test_value() {
+        assert_eq!(PackingBox::new(5, 6).value(), 30.0);
+        assert_eq!(PackingBox::new(5, 6).with_value(2.5).value(), 2.5);
     }
 
     #[test]
@@ -109,6 +311,22 @@ mod tests {
         assert_eq!(box_item.bucketid, Some(1));
     }
 
+    #[test]
+    fn test_placement() {
+        let mut box_item = PackingBox::new(5, 6);
+        assert_eq!(box_item.placement(), None);
+        box_item.place(10, 20, 1);
+        assert_eq!(
+            box_item.placement(),
+            Some(Placement {
+                originx: 10,
+                originy: 20,
+                bucketid: 1,
+                rotated: false
+            })
+        );
+    }
+
     #[test]
     fn test_get_coords() {
         let mut box_item = PackingBox::new(5, 6);
@@ -126,6 +344,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_overlap() {
         let mut box1 = PackingBox::new(5, 6);
         box1.place(10, 20, 1);
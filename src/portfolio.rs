@@ -0,0 +1,439 @@
+//! The `portfolio` module runs many `MaxRects` configurations in parallel and keeps the best
+//! packing, trading CPU time for packing quality.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::bounds::{Gap, LowerBounds};
+use crate::bucket::Bucket;
+use crate::calculate_packed_percentage;
+use crate::heuristic::Heuristic;
+use crate::max_rects::{MaxRects, SearchMode};
+use crate::packing_box::PackingBox;
+use crate::sort::{SortKey, SortStrategy};
+
+/// How the results of the configurations are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    /// Fewest boxes left unplaced, then fewest bins with at least one box in them.
+    #[default]
+    BinsUsed,
+    /// Largest share of the bins' total area covered by placed boxes.
+    PackedPercentage,
+    /// Fewest boxes left unplaced, then the smallest total area of the rectangles enclosing
+    /// the boxes in each bin.
+    BoundingArea,
+    /// Largest total `value` of the placed boxes, whatever is left unplaced.
+    PlacedValue,
+}
+
+impl Objective {
+    /// Scores a packing. Lower is better.
+    ///
+    /// The first element counts unplaced boxes for the objectives that rank them first, and
+    /// is always 0 for `PackedPercentage` and `PlacedValue`.
+    pub fn score(
+        &self,
+        placed: &[PackingBox],
+        unplaced: &[PackingBox],
+        bins: &[Bucket],
+    ) -> (usize, f64) {
+        match self {
+            Objective::BinsUsed => (unplaced.len(), bins_used(placed) as f64),
+            Objective::PackedPercentage => (0, -calculate_packed_percentage(placed, bins) as f64),
+            Objective::BoundingArea => (unplaced.len(), bounding_area(placed) as f64),
+            Objective::PlacedValue => (0, -placed.iter().map(|b| b.value()).sum::<f64>()),
+        }
+    }
+}
+
+/// The order in which boxes are handed to the packer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Order {
+    /// The boxes as supplied.
+    Given,
+    /// The boxes sorted by a pre-sort.
+    Sorted(SortStrategy),
+    /// A random shuffle, reproducible from the seed.
+    Random(u64),
+}
+
+impl Order {
+    /// The boxes as supplied, then sorted largest first by area, perimeter, longest side,
+    /// width and height.
+    pub fn defaults() -> Vec<Order> {
+        let mut orders = vec![Order::Given];
+        orders.extend(
+            [
+                SortKey::Area,
+                SortKey::Perimeter,
+                SortKey::MaxSide,
+                SortKey::Width,
+                SortKey::Height,
+            ]
+            .map(|key| Order::Sorted(SortStrategy::descending(key))),
+        );
+        orders
+    }
+
+    /// Reorders `boxes` in place.
+    pub fn apply(&self, boxes: &mut [PackingBox]) {
+        match self {
+            Order::Given => {}
+            Order::Sorted(sort) => sort.apply(boxes),
+            Order::Random(seed) => boxes.shuffle(&mut StdRng::seed_from_u64(*seed)),
+        }
+    }
+}
+
+/// One way of running `MaxRects`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Configuration {
+    pub heuristic: Heuristic,
+    pub search: SearchMode,
+    pub order: Order,
+}
+
+/// A `PortfolioResult` object contains the following fields:
+/// - `configuration`: The configuration that produced the packing.
+/// - `placed`: The placed boxes.
+/// - `unplaced`: The boxes that could not be placed.
+/// - `free`: The free space left in the bins.
+/// - `gap`: How far the number of bins used is from the lower bound, if the bins all have
+///   the same size.
+#[derive(Debug, Clone)]
+pub struct PortfolioResult {
+    pub configuration: Configuration,
+    pub placed: Vec<PackingBox>,
+    pub unplaced: Vec<PackingBox>,
+    pub free: Vec<Bucket>,
+    pub gap: Option<Gap>,
+}
+
+/// A `Portfolio` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `objective`: How the packings are compared.
+/// - `heuristics`: The heuristics to try.
+/// - `orders`: The box orders to try with the in-order search.
+/// - `packer`: The template for every run, see [`MaxRects::renew`]. Its heuristic, search
+///   mode and pre-sort are overridden by each configuration.
+#[derive(Clone)]
+pub struct Portfolio {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub objective: Objective,
+    pub heuristics: Vec<Heuristic>,
+    pub orders: Vec<Order>,
+    pub packer: MaxRects,
+}
+
+impl Portfolio {
+    /// Constructs a new `Portfolio` that tries every built-in heuristic with the orders from
+    /// `Order::defaults`.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Portfolio` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        Self {
+            boxes,
+            bins,
+            objective: Objective::default(),
+            heuristics: Heuristic::ALL.to_vec(),
+            orders: Order::defaults(),
+            packer: MaxRects::new(vec![], vec![]),
+        }
+    }
+    /// Sets how the packings are compared.
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+    /// Sets the heuristics to try.
+    pub fn with_heuristics(mut self, heuristics: Vec<Heuristic>) -> Self {
+        self.heuristics = heuristics;
+        self
+    }
+    /// Sets the box orders to try.
+    pub fn with_orders(mut self, orders: Vec<Order>) -> Self {
+        self.orders = orders;
+        self
+    }
+    /// Adds `count` random orders, seeded with `seed`, `seed + 1` and so on.
+    pub fn with_random_orders(mut self, count: u64, seed: u64) -> Self {
+        self.orders
+            .extend((0..count).map(|i| Order::Random(seed.wrapping_add(i))));
+        self
+    }
+    /// Sets the template for every run.
+    pub fn with_packer(mut self, packer: MaxRects) -> Self {
+        self.packer = packer;
+        self
+    }
+    /// Lists the configurations `solve` runs.
+    ///
+    /// Each heuristic is run once with the global and once with the bin-by-bin search, which
+    /// do not depend on box order, and once with the in-order search for every order.
+    pub fn configurations(&self) -> Vec<Configuration> {
+        let mut configurations = vec![];
+        for &heuristic in &self.heuristics {
+            for search in [SearchMode::Global, SearchMode::BinByBin] {
+                configurations.push(Configuration {
+                    heuristic,
+                    search,
+                    order: Order::Given,
+                });
+            }
+            for order in &self.orders {
+                configurations.push(Configuration {
+                    heuristic,
+                    search: SearchMode::InOrder,
+                    order: order.clone(),
+                });
+            }
+        }
+        configurations
+    }
+    /// Packs the boxes with one configuration, measuring the result against `bounds`.
+    fn attempt(
+        &self,
+        configuration: Configuration,
+        bounds: Option<LowerBounds>,
+    ) -> PortfolioResult {
+        let mut boxes = self.boxes.clone();
+        configuration.order.apply(&mut boxes);
+        let mut packer = self
+            .packer
+            .renew(boxes, self.bins.clone())
+            .with_heuristic(configuration.heuristic)
+            .with_search(configuration.search);
+        packer.sort = None;
+        let (placed, unplaced, free) = packer.place();
+        PortfolioResult {
+            configuration,
+            gap: bounds.map(|b| b.gap(bins_used(&placed))),
+            placed,
+            unplaced,
+            free,
+        }
+    }
+    /// Runs every configuration in parallel and returns the best packing.
+    ///
+    /// Ties go to the configuration listed first by `configurations`, so the result does not
+    /// depend on thread scheduling.
+    ///
+    /// # Returns
+    /// The best packing, the configuration that produced it and its gap to the lower bound,
+    /// or `None` if there are no heuristics to try.
+    ///
+    /// # Examples
+    /// ```
+    /// use max_rects::{bucket::Bucket, packing_box::PackingBox, portfolio::Portfolio};
+    ///
+    /// let boxes = vec![PackingBox::new(5, 5); 4];
+    /// let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(6, 6, 0, 0, 1)];
+    /// let best = Portfolio::new(boxes, bins).solve().unwrap();
+    ///
+    /// assert!(best.unplaced.is_empty());
+    /// assert!(best.placed.iter().all(|b| b.bucketid == Some(0)));
+    /// ```
+    pub fn solve(&self) -> Option<PortfolioResult> {
+        let bounds = LowerBounds::for_bins(&self.boxes, &self.bins, self.packer.allow_rotation);
+        self.configurations()
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, configuration)| {
+                let result = self.attempt(configuration, bounds);
+                let score = self
+                    .objective
+                    .score(&result.placed, &result.unplaced, &self.bins);
+                (score, i, result)
+            })
+            .min_by(|a, b| {
+                a.0 .0
+                    .cmp(&b.0 .0)
+                    .then(a.0 .1.total_cmp(&b.0 .1))
+                    .then(a.1.cmp(&b.1))
+            })
+            .map(|(_, _, result)| result)
+    }
+}
+
+/// Counts the bins holding at least one placed box.
+pub fn bins_used(placed: &[PackingBox]) -> usize {
+    let mut bucketids: Vec<i32> = placed.iter().filter_map(|b| b.bucketid).collect();
+    bucketids.sort_unstable();
+    bucketids.dedup();
+    bucketids.len()
+}
+
+/// Sums, over every bin, the area of the smallest rectangle enclosing the boxes placed in it.
+pub fn bounding_area(placed: &[PackingBox]) -> i64 {
+    let mut bounds: Vec<(i32, (i32, i32, i32, i32))> = vec![];
+    for box_item in placed {
+        let Some(bucketid) = box_item.bucketid else {
+            continue;
+        };
+        let (x1, x2, y1, y2) = box_item.get_coords();
+        match bounds.iter_mut().find(|(id, _)| *id == bucketid) {
+            Some((_, b)) => *b = (b.0.min(x1), b.1.max(x2), b.2.min(y1), b.3.max(y2)),
+            None => bounds.push((bucketid, (x1, x2, y1, y2))),
+        }
+    }
+    bounds
+        .iter()
+        .map(|(_, (x1, x2, y1, y2))| (x2 - x1) as i64 * (y2 - y1) as i64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes() -> Vec<PackingBox> {
+        vec![
+            PackingBox::new(3, 7),
+            PackingBox::new(8, 2),
+            PackingBox::new(5, 5),
+            PackingBox::new(2, 9),
+            PackingBox::new(6, 4),
+        ]
+    }
+
+    #[test]
+    fn test_configurations() {
+        let portfolio = Portfolio::new(boxes(), vec![]);
+        assert_eq!(portfolio.configurations().len(), 5 * 8);
+
+        let portfolio = portfolio
+            .with_heuristics(vec![Heuristic::BestAreaFit])
+            .with_random_orders(3, 7);
+        let configurations = portfolio.configurations();
+        assert_eq!(configurations.len(), 2 + 9);
+        assert_eq!(configurations[10].order, Order::Random(9));
+    }
+
+    #[test]
+    fn test_orders() {
+        let mut sorted = boxes();
+        Order::Sorted(SortStrategy::descending(SortKey::Area)).apply(&mut sorted);
+        assert_eq!((sorted[0].width, sorted[4].width), (5, 8));
+
+        Order::Sorted(SortStrategy::descending(SortKey::MaxSide)).apply(&mut sorted);
+        assert_eq!(sorted[0].height, 9);
+
+        let mut first = boxes();
+        let mut second = boxes();
+        Order::Random(42).apply(&mut first);
+        Order::Random(42).apply(&mut second);
+        let sizes = |b: &[PackingBox]| b.iter().map(|b| (b.width, b.height)).collect::<Vec<_>>();
+        assert_eq!(sizes(&first), sizes(&second));
+    }
+
+    #[test]
+    fn test_solve_uses_fewest_bins() {
+        let boxes = vec![PackingBox::new(5, 5); 4];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(6, 6, 0, 0, 1)];
+
+        let (placed, _, _) = MaxRects::new(boxes.clone(), bins.clone()).place();
+        assert_eq!(bins_used(&placed), 2);
+
+        let best = Portfolio::new(boxes, bins).solve().unwrap();
+        assert!(best.unplaced.is_empty());
+        assert_eq!(bins_used(&best.placed), 1);
+        // The bins differ in size, so there is no bound to measure against.
+        assert!(best.gap.is_none());
+    }
+
+    #[test]
+    fn test_solve_reports_gap() {
+        let bins = vec![Bucket::new(10, 10, 0, 0, 0), Bucket::new(10, 10, 0, 0, 1)];
+        let best = Portfolio::new(vec![PackingBox::new(6, 6); 2], bins)
+            .solve()
+            .unwrap();
+        let gap = best.gap.unwrap();
+
+        assert_eq!((gap.bins_used, gap.lower_bound, gap.absolute), (2, 2, 0));
+    }
+
+    #[test]
+    fn test_solve_is_no_worse_than_any_configuration() {
+        let bins = vec![Bucket::new(12, 12, 0, 0, 0)];
+        let portfolio = Portfolio::new(boxes(), bins.clone())
+            .with_objective(Objective::BoundingArea)
+            .with_random_orders(4, 1);
+        let best = portfolio.solve().unwrap();
+        let best_score = Objective::BoundingArea.score(&best.placed, &best.unplaced, &bins);
+
+        for configuration in portfolio.configurations() {
+            let mut boxes = boxes();
+            configuration.order.apply(&mut boxes);
+            let (placed, unplaced, _) = MaxRects::new(boxes, bins.clone())
+                .with_heuristic(configuration.heuristic)
+                .with_search(configuration.search)
+                .place();
+            let score = Objective::BoundingArea.score(&placed, &unplaced, &bins);
+            assert!(best_score.0 < score.0 || (best_score.0 == score.0 && best_score.1 <= score.1));
+        }
+    }
+
+    #[test]
+    fn test_orders_override_packer_sort() {
+        let boxes = vec![
+            PackingBox::new(2, 2),
+            PackingBox::new(5, 5),
+            PackingBox::new(3, 3),
+        ];
+        let packer = MaxRects::new(vec![], vec![]).with_sort(SortStrategy::default());
+        let portfolio =
+            Portfolio::new(boxes, vec![Bucket::new(20, 20, 0, 0, 0)]).with_packer(packer);
+        let widths = |order: Order| -> Vec<i32> {
+            let configuration = Configuration {
+                heuristic: Heuristic::default(),
+                search: SearchMode::InOrder,
+                order,
+            };
+            let result = portfolio.attempt(configuration, None);
+            result.placed.iter().map(|b| b.width).collect()
+        };
+
+        assert_eq!(widths(Order::Given), vec![2, 5, 3]);
+        assert_eq!(
+            widths(Order::Sorted(SortStrategy::ascending(SortKey::Area))),
+            vec![2, 3, 5]
+        );
+    }
+
+    #[test]
+    fn test_solve_without_heuristics() {
+        let portfolio = Portfolio::new(boxes(), vec![]).with_heuristics(vec![]);
+        assert!(portfolio.solve().is_none());
+    }
+
+    #[test]
+    fn test_bounding_area() {
+        let mut a = PackingBox::new(2, 2);
+        a.place(0, 8, 0);
+        let mut b = PackingBox::new(3, 1);
+        b.place(4, 9, 0);
+        let mut c = PackingBox::new(5, 5);
+        c.place(0, 0, 1);
+
+        assert_eq!(bounding_area(&[a, b, c]), 7 * 2 + 25);
+    }
+
+    #[test]
+    fn test_placed_value() {
+        let placed = vec![PackingBox::new(2, 2).with_value(5.0), PackingBox::new(3, 1)];
+        let unplaced = vec![PackingBox::new(9, 9)];
+
+        let score = Objective::PlacedValue.score(&placed, &unplaced, &[]);
+        assert_eq!(score, (0, -8.0));
+    }
+}
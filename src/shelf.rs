@@ -0,0 +1,539 @@
+//! The `shelf` module provides the Shelf family of bin packing algorithms.
+//! Boxes are laid out left to right on horizontal shelves stacked from the bottom of each bin,
+//! which gives a predictable row layout and very fast placement. Space left above boxes that
+//! are shorter than their shelf can optionally be recovered through a waste map.
+
+use std::collections::HashMap;
+
+use crate::bucket::Bucket;
+use crate::grouping::{self, Packed};
+use crate::guillotine::Guillotine;
+use crate::packing_box::PackingBox;
+
+/// The rule used to choose which shelf a box goes onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShelfRule {
+    /// Only the topmost shelf of each bin is open; opening a new shelf closes the old one.
+    #[default]
+    NextFit,
+    /// Uses the first shelf the box fits on.
+    FirstFit,
+    /// Uses the shelf that leaves the least unused area next to and above the box.
+    BestAreaFit,
+    /// Uses the shelf whose height is closest to the box's height.
+    BestHeightFit,
+    /// Uses the shelf with the least width left over after the box.
+    BestWidthFit,
+}
+
+/// A shelf `height` units tall, resting `level` units up from the bottom of its bin, with
+/// `used` units of its width already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Row {
+    level: i32,
+    height: i32,
+    used: i32,
+}
+
+/// A `Shelf` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `rule`: The rule used to choose a shelf for each box.
+/// - `allow_rotation`: Whether boxes may be turned by 90 degrees to fit.
+/// - `use_waste_map`: Whether space left above boxes on their shelves is kept for later boxes.
+/// - `height_rounding`: New shelves are made a multiple of this height, leaving headroom for
+///   slightly taller boxes later on.
+#[derive(Debug, Clone)]
+pub struct Shelf {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub rule: ShelfRule,
+    pub allow_rotation: bool,
+    pub use_waste_map: bool,
+    pub height_rounding: i32,
+    rows: Vec<Vec<Row>>,
+    waste: Guillotine,
+    /// The bin each group is held to while `place` keeps groups together.
+    pins: HashMap<String, i32>,
+}
+
+impl Shelf {
+    /// Constructs a new `Shelf` instance using the `NextFit` rule, no height rounding and no
+    /// waste map.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Shelf` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        Self {
+            boxes,
+            rows: vec![vec![]; bins.len()],
+            bins,
+            rule: ShelfRule::default(),
+            allow_rotation: false,
+            use_waste_map: false,
+            height_rounding: 1,
+            waste: Guillotine::new(vec![], vec![]),
+            pins: HashMap::new(),
+        }
+    }
+    /// Sets the rule used to choose a shelf for each box.
+    pub fn with_rule(mut self, rule: ShelfRule) -> Self {
+        self.rule = rule;
+        self
+    }
+    /// Allows or forbids turning boxes by 90 degrees during placement.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self.waste.allow_rotation = allow_rotation;
+        self
+    }
+    /// Enables or disables the waste map that recovers space left above boxes.
+    pub fn with_waste_map(mut self, use_waste_map: bool) -> Self {
+        self.use_waste_map = use_waste_map;
+        self
+    }
+    /// Rounds the height of every new shelf up to a multiple of `height_rounding`.
+    pub fn with_height_rounding(mut self, height_rounding: i32) -> Self {
+        self.height_rounding = i32::max(height_rounding, 1);
+        self
+    }
+    /// Finds where a `width` by `height` box fits on a shelf at `level`, starting `used` units
+    /// from the left of `bin` and moving right past any obstacle in its way.
+    ///
+    /// # Returns
+    /// The offset of the box from the left of the bin.
+    fn slot(bin: &Bucket, level: i32, used: i32, width: i32, height: i32) -> Option<i32> {
+        let bottom = bin.originy + bin.height;
+        let mut x = used;
+        while let Some(obstacle) = bin.obstacles.iter().find(|o| {
+            let (ox1, ox2, oy1, oy2) = o.get_coords();
+            let left = bin.originx + x;
+            ox1 < left + width
+                && left < ox2
+                && bottom - oy2 < level + height
+                && level < bottom - oy1
+        }) {
+            x = obstacle.originx + obstacle.width - bin.originx;
+        }
+        (x + width <= bin.width).then_some(x)
+    }
+    /// Finds the best existing shelf for `box_item` in the bins it allows, keeping to the bin
+    /// `home` when it is set.
+    ///
+    /// # Returns
+    /// The bin index, the row index, the offset of the box along the shelf and whether the
+    /// box has to be turned.
+    fn best_row(
+        &self,
+        box_item: &PackingBox,
+        home: Option<i32>,
+    ) -> Option<(usize, usize, i32, bool)> {
+        let mut best: Option<(i64, usize, usize, i32, bool)> = None;
+
+        for (b, (bin, rows)) in self.bins.iter().zip(&self.rows).enumerate() {
+            if !box_item.allows(bin.bucketid) || home.is_some_and(|id| bin.bucketid != id) {
+                continue;
+            }
+            let open = match self.rule {
+                ShelfRule::NextFit => rows.len().saturating_sub(1),
+                _ => 0,
+            };
+            for (r, row) in rows.iter().enumerate().skip(open) {
+                for (width, height, rotate) in box_item.orientations(self.allow_rotation, bin.grain)
+                {
+                    if height > row.height {
+                        continue;
+                    }
+                    let Some(x) = Self::slot(bin, row.level, row.used, width, height) else {
+                        continue;
+                    };
+                    let spare = bin.width - x - width;
+                    let score = match self.rule {
+                        ShelfRule::NextFit | ShelfRule::FirstFit => return Some((b, r, x, rotate)),
+                        ShelfRule::BestAreaFit => {
+                            (bin.width - row.used) as i64 * row.height as i64
+                                - width as i64 * height as i64
+                        }
+                        ShelfRule::BestHeightFit => (row.height - height) as i64,
+                        ShelfRule::BestWidthFit => spare as i64,
+                    };
+                    if best.is_none_or(|(s, _, _, _, _)| score < s) {
+                        best = Some((score, b, r, x, rotate));
+                    }
+                }
+            }
+        }
+        best.map(|(_, b, r, x, rotate)| (b, r, x, rotate))
+    }
+    /// Opens a shelf for `box_item` in the first bin with enough headroom that it allows, and
+    /// in `home` if that is set, laying the box on its long side when rotation allows so the
+    /// shelf stays as low as possible. A shelf that would run into an obstacle is raised to
+    /// the top of the obstacle if it has to be.
+    ///
+    /// # Returns
+    /// The bin index, the new row index, the offset of the box along the shelf and whether
+    /// the box has to be turned.
+    fn open_row(
+        &mut self,
+        box_item: &PackingBox,
+        home: Option<i32>,
+    ) -> Option<(usize, usize, i32, bool)> {
+        for b in 0..self.bins.len() {
+            let bin = &self.bins[b];
+            if !box_item.allows(bin.bucketid) || home.is_some_and(|id| bin.bucketid != id) {
+                continue;
+            }
+            let top = self.rows[b].last().map_or(0, |row| row.level + row.height);
+            let bottom = bin.originy + bin.height;
+            let levels: Vec<i32> = std::iter::once(top)
+                .chain(bin.obstacles.iter().map(|o| bottom - o.originy))
+                .filter(|&level| level >= top)
+                .collect();
+
+            let fit = box_item
+                .orientations(self.allow_rotation, bin.grain)
+                .into_iter()
+                .filter_map(|(width, height, rotate)| {
+                    levels
+                        .iter()
+                        .filter(|&&level| level + height <= bin.height)
+                        .filter_map(|&level| {
+                            Self::slot(bin, level, 0, width, height).map(|x| (level, x))
+                        })
+                        .min()
+                        .map(|(level, x)| (level, x, height, rotate))
+                })
+                .min_by_key(|&(level, _, height, _)| level + height);
+
+            if let Some((level, x, height, rotate)) = fit {
+                let rounded = (height + self.height_rounding - 1) / self.height_rounding
+                    * self.height_rounding;
+                let row = Row {
+                    level,
+                    height: i32::min(rounded, bin.height - level),
+                    used: 0,
+                };
+
+                if self.rule == ShelfRule::NextFit {
+                    if let Some(closed) = self.rows[b].last_mut() {
+                        if self.use_waste_map && closed.used < bin.width {
+                            let rest = Bucket::new(
+                                bin.width - closed.used,
+                                closed.height,
+                                bin.originx + closed.used,
+                                bin.originy + bin.height - closed.level - closed.height,
+                                bin.bucketid,
+                            );
+                            self.waste.bins.extend(rest.subtract(&bin.obstacles));
+                            closed.used = bin.width;
+                        }
+                    }
+                }
+
+                self.rows[b].push(row);
+                return Some((b, self.rows[b].len() - 1, x, rotate));
+            }
+        }
+        None
+    }
+    /// Returns the free space on and above the shelves together with any space in the
+    /// waste map.
+    fn free_space(&self) -> Vec<Bucket> {
+        let mut free = self.waste.bins.clone();
+        for (bin, rows) in self.bins.iter().zip(&self.rows) {
+            for row in rows.iter().filter(|row| row.used < bin.width) {
+                let rest = Bucket::new(
+                    bin.width - row.used,
+                    row.height,
+                    bin.originx + row.used,
+                    bin.originy + bin.height - row.level - row.height,
+                    bin.bucketid,
+                );
+                free.extend(rest.subtract(&bin.obstacles));
+            }
+            let top = rows.last().map_or(0, |row| row.level + row.height);
+            if top < bin.height {
+                let above = Bucket::new(
+                    bin.width,
+                    bin.height - top,
+                    bin.originx,
+                    bin.originy,
+                    bin.bucketid,
+                );
+                free.extend(above.subtract(&bin.obstacles));
+            }
+        }
+        free
+    }
+    /// Attempts to place the boxes into the bins.
+    ///
+    /// Boxes are taken in the order given. Each one goes into the waste map if it fits there,
+    /// otherwise onto the shelf chosen by `rule`, otherwise onto a newly opened shelf. Boxes
+    /// sharing a `group` are placed in the same bin or not at all.
+    ///
+    /// # Returns
+    /// A tuple of three vectors:
+    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
+    /// - A vector of `Bucket` objects representing the free space left in the bins.
+    pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        if !self.boxes.iter().any(|b| b.group.is_some()) {
+            return self.fill();
+        }
+        let start = self.clone();
+        let order = grouping::bin_order(&self.bins);
+        let (placed, unplaced, free) =
+            grouping::place_grouped(&start.boxes, &order, |boxes, pins| {
+                *self = start.clone();
+                self.boxes = boxes;
+                self.pins = pins.clone();
+                self.fill()
+            });
+        self.pins.clear();
+        self.boxes = unplaced.clone();
+        (placed, unplaced, free)
+    }
+    /// Places the boxes in order, sending each box that belongs to a group to its group's bin.
+    fn fill(&mut self) -> Packed {
+        let mut placed = vec![];
+        let mut unplaced = vec![];
+        let containers: HashMap<i32, Bucket> = self
+            .bins
+            .iter()
+            .map(|bin| (bin.bucketid, bin.clone()))
+            .collect();
+
+        for mut box_item in std::mem::take(&mut self.boxes) {
+            let home = grouping::home(&box_item, &placed, &self.pins);
+            if self.use_waste_map {
+                if let Some((_, idx, rotate)) = self.waste.best_fit(&box_item, &containers, home) {
+                    placed.push(self.waste.commit(box_item, idx, rotate));
+                    continue;
+                }
+            }
+
+            let found = match self.best_row(&box_item, home) {
+                Some(found) => Some(found),
+                None => self.open_row(&box_item, home),
+            };
+            let Some((b, r, x, rotate)) = found else {
+                unplaced.push(box_item);
+                continue;
+            };
+
+            if rotate {
+                box_item.rotate();
+            }
+            let bin = &self.bins[b];
+            let row = &mut self.rows[b][r];
+            box_item.place(
+                bin.originx + x,
+                bin.originy + bin.height - row.level - box_item.height,
+                bin.bucketid,
+            );
+            if self.use_waste_map && box_item.height < row.height {
+                let headroom = Bucket::new(
+                    box_item.width,
+                    row.height - box_item.height,
+                    bin.originx + x,
+                    bin.originy + bin.height - row.level - row.height,
+                    bin.bucketid,
+                );
+                self.waste.bins.extend(headroom.subtract(&bin.obstacles));
+            }
+            row.used = x + box_item.width;
+            placed.push(box_item);
+        }
+
+        self.boxes = unplaced;
+        (placed, self.boxes.clone(), self.free_space())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_disjoint(placed: &[PackingBox], free: &[Bucket]) {
+        for (i, a) in placed.iter().enumerate() {
+            let (x1, x2, y1, y2) = a.get_coords();
+            let rect = Bucket::new(x2 - x1, y2 - y1, x1, y1, a.bucketid.unwrap());
+            assert!(placed.iter().skip(i + 1).all(|b| !b.overlap(&rect)));
+            assert!(free.iter().all(|f| !a.overlap(f)));
+        }
+    }
+
+    #[test]
+    fn test_next_fit_rows() {
+        let boxes = vec![
+            PackingBox::new(6, 3),
+            PackingBox::new(3, 2),
+            PackingBox::new(5, 4),
+            PackingBox::new(1, 1),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut shelf = Shelf::new(boxes, bins);
+        let (placed, remaining, free) = shelf.place();
+
+        assert!(remaining.is_empty());
+        assert_eq!(placed[0].get_coords(), (0, 6, 7, 10));
+        assert_eq!(placed[1].get_coords(), (6, 9, 8, 10));
+        assert_eq!(placed[2].get_coords(), (0, 5, 3, 7));
+        // Next fit never goes back to the first shelf, even though there is room there.
+        assert_eq!(placed[3].get_coords(), (5, 6, 6, 7));
+        assert_disjoint(&placed, &free);
+    }
+
+    #[test]
+    fn test_first_fit_reuses_lower_shelf() {
+        let boxes = vec![
+            PackingBox::new(6, 3),
+            PackingBox::new(5, 4),
+            PackingBox::new(3, 1),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut shelf = Shelf::new(boxes, bins).with_rule(ShelfRule::FirstFit);
+        let (placed, _, _) = shelf.place();
+
+        assert_eq!(placed[2].get_coords(), (6, 9, 9, 10));
+    }
+
+    #[test]
+    fn test_best_height_fit() {
+        let boxes = vec![
+            PackingBox::new(4, 5),
+            PackingBox::new(8, 2),
+            PackingBox::new(2, 2),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut shelf = Shelf::new(boxes, bins).with_rule(ShelfRule::BestHeightFit);
+        let (placed, _, _) = shelf.place();
+
+        assert_eq!(placed[2].get_coords(), (8, 10, 3, 5));
+    }
+
+    #[test]
+    fn test_height_rounding() {
+        let boxes = vec![PackingBox::new(5, 3), PackingBox::new(5, 4)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+
+        let mut plain = Shelf::new(boxes.clone(), bins.clone());
+        let (placed, _, _) = plain.place();
+        assert_eq!(placed[1].get_coords(), (0, 5, 3, 7));
+
+        let mut rounded = Shelf::new(boxes, bins).with_height_rounding(4);
+        let (placed, _, free) = rounded.place();
+        assert_eq!(placed[1].get_coords(), (5, 10, 6, 10));
+        assert_disjoint(&placed, &free);
+    }
+
+    #[test]
+    fn test_waste_map_fills_headroom() {
+        let boxes = vec![
+            PackingBox::new(5, 8),
+            PackingBox::new(5, 2),
+            PackingBox::new(5, 6),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+
+        let mut plain = Shelf::new(boxes.clone(), bins.clone());
+        let (_, remaining, _) = plain.place();
+        assert_eq!(remaining.len(), 1);
+
+        let mut waste = Shelf::new(boxes, bins).with_waste_map(true);
+        let (placed, remaining, free) = waste.place();
+        assert!(remaining.is_empty());
+        assert_disjoint(&placed, &free);
+    }
+
+    #[test]
+    fn test_obstacles() {
+        let sheet = Bucket::new(10, 10, 0, 0, 1).with_obstacle(2, 2, 4, 4);
+        let obstacle = sheet.obstacles[0].clone();
+
+        let mut shelf = Shelf::new(vec![PackingBox::new(5, 5)], vec![sheet.clone()]);
+        assert!(shelf.place().0.is_empty());
+
+        for use_waste_map in [false, true] {
+            let boxes = vec![PackingBox::new(4, 4); 4];
+            let mut shelf = Shelf::new(boxes, vec![sheet.clone()]).with_waste_map(use_waste_map);
+            let (placed, remaining, free) = shelf.place();
+
+            assert!(remaining.is_empty());
+            // The last box moves along its shelf past the obstacle.
+            assert_eq!(placed[3].get_coords(), (6, 10, 2, 6));
+            assert!(placed.iter().all(|b| !b.overlap(&obstacle)));
+            assert!(free.iter().all(|f| !f.overlap(&obstacle)));
+            assert_disjoint(&placed, &free);
+        }
+
+        // A shelf that would run into a bar across the bin opens above it instead.
+        let sheet = Bucket::new(10, 10, 0, 0, 1).with_obstacle(10, 2, 0, 6);
+        let mut shelf = Shelf::new(vec![PackingBox::new(10, 3)], vec![sheet]);
+        let (placed, _, _) = shelf.place();
+        assert_eq!(placed[0].get_coords(), (0, 10, 3, 6));
+    }
+
+    #[test]
+    fn test_allowed_bins() {
+        let boxes = vec![
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5).with_allowed_bins([2]),
+        ];
+        let bins = vec![Bucket::new(5, 5, 0, 0, 0), Bucket::new(5, 5, 0, 0, 1)];
+        let mut shelf = Shelf::new(boxes, bins);
+        let (placed, remaining, _) = shelf.place();
+
+        assert_eq!((placed.len(), remaining.len()), (2, 2));
+        assert!(placed.iter().all(|b| b.allows(b.bucketid.unwrap())));
+        assert!(remaining.iter().any(|b| b.allows(2) && !b.allows(1)));
+    }
+
+    #[test]
+    fn test_groups_share_a_bin() {
+        let mut boxes = vec![PackingBox::new(5, 5).with_group("a"); 3];
+        boxes.extend(vec![PackingBox::new(5, 5).with_group("b"); 2]);
+        boxes.push(PackingBox::new(5, 5));
+        boxes.push(PackingBox::new(5, 5).with_group("c"));
+        boxes.push(PackingBox::new(6, 6).with_group("c"));
+        let bins = vec![
+            Bucket::new(10, 5, 0, 0, 0),
+            Bucket::new(10, 10, 0, 0, 1),
+            Bucket::new(5, 5, 0, 0, 2),
+        ];
+        let mut shelf = Shelf::new(boxes, bins);
+        let (placed, remaining, _) = shelf.place();
+
+        assert_eq!(placed.len(), 6);
+        // Group "c" fits whole in no bin, so neither of its boxes is placed.
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().all(|b| b.group.as_deref() == Some("c")));
+        for group in ["a", "b"] {
+            let mut ids: Vec<_> = placed
+                .iter()
+                .filter(|b| b.group.as_deref() == Some(group))
+                .map(|b| b.bucketid)
+                .collect();
+            ids.dedup();
+            assert_eq!(ids.len(), 1, "{}", group);
+        }
+        assert!(shelf.pins.is_empty());
+    }
+
+    #[test]
+    fn test_rotation_lays_boxes_flat() {
+        let boxes = vec![PackingBox::new(2, 8)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut shelf = Shelf::new(boxes, bins).with_rotation(true);
+        let (placed, _, _) = shelf.place();
+
+        assert!(placed[0].rotated);
+        assert_eq!(placed[0].get_coords(), (0, 8, 8, 10));
+    }
+}
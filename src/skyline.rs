@@ -0,0 +1,480 @@
+//! The `skyline` module provides an implementation of the Skyline bin packing algorithm.
+//! Each bin keeps only the outline of its packed boxes, which makes placement much cheaper
+//! than maintaining a list of maximal free rectangles. Space trapped under the outline can
+//! optionally be recovered through a waste map.
+
+use std::collections::HashMap;
+
+use crate::bucket::Bucket;
+use crate::grouping::{self, Packed};
+use crate::guillotine::Guillotine;
+use crate::packing_box::PackingBox;
+
+/// The rule used to choose where along the skyline a box goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkylineRule {
+    /// Keeps the top of the box as low as possible, then prefers narrower segments.
+    #[default]
+    BottomLeft,
+    /// Minimizes the space trapped underneath the box, then keeps its top as low as possible.
+    MinWasteFit,
+}
+
+/// A horizontal stretch of the outline, `level` units up from the bottom of its bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    x: i32,
+    width: i32,
+    level: i32,
+}
+
+/// A candidate position: the score, the bin index, the segment index, the resting level and
+/// whether the box has to be turned.
+type Candidate = ((i64, i32), usize, usize, i32, bool);
+
+/// A `Skyline` object contains the following fields:
+/// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+/// - `bins`: A vector of `Bucket` objects representing the available bins.
+/// - `rule`: The rule used to choose a position along the skyline.
+/// - `allow_rotation`: Whether boxes may be turned by 90 degrees to fit.
+/// - `use_waste_map`: Whether space trapped under the skyline is kept for later boxes.
+#[derive(Debug, Clone)]
+pub struct Skyline {
+    pub boxes: Vec<PackingBox>,
+    pub bins: Vec<Bucket>,
+    pub rule: SkylineRule,
+    pub allow_rotation: bool,
+    pub use_waste_map: bool,
+    skylines: Vec<Vec<Segment>>,
+    waste: Guillotine,
+    /// The bin each group is held to while `place` keeps groups together.
+    pins: HashMap<String, i32>,
+}
+
+impl Skyline {
+    /// Constructs a new `Skyline` instance using the `BottomLeft` rule and no waste map.
+    ///
+    /// # Arguments
+    /// - `boxes`: A vector of `PackingBox` objects representing the rectangles to be placed.
+    /// - `bins`: A vector of `Bucket` objects representing the available bins.
+    ///
+    /// # Returns
+    /// A new `Skyline` object.
+    pub fn new(boxes: Vec<PackingBox>, bins: Vec<Bucket>) -> Self {
+        let skylines = bins
+            .iter()
+            .map(|bin| {
+                vec![Segment {
+                    x: bin.originx,
+                    width: bin.width,
+                    level: 0,
+                }]
+            })
+            .collect();
+        Self {
+            boxes,
+            bins,
+            rule: SkylineRule::default(),
+            allow_rotation: false,
+            use_waste_map: false,
+            skylines,
+            waste: Guillotine::new(vec![], vec![]),
+            pins: HashMap::new(),
+        }
+    }
+    /// Sets the rule used to choose a position along the skyline.
+    pub fn with_rule(mut self, rule: SkylineRule) -> Self {
+        self.rule = rule;
+        self
+    }
+    /// Allows or forbids turning boxes by 90 degrees during placement.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self.waste.allow_rotation = allow_rotation;
+        self
+    }
+    /// Enables or disables the waste map that recovers space trapped under the skyline.
+    pub fn with_waste_map(mut self, use_waste_map: bool) -> Self {
+        self.use_waste_map = use_waste_map;
+        self
+    }
+    /// Checks whether a `width` by `height` box can sit on the segment at `index`, lifting it
+    /// above any obstacle in its way.
+    ///
+    /// # Returns
+    /// The level the bottom of the box would rest at and the area trapped underneath it.
+    fn fit(
+        bin: &Bucket,
+        skyline: &[Segment],
+        index: usize,
+        width: i32,
+        height: i32,
+    ) -> Option<(i32, i64)> {
+        let x = skyline[index].x;
+        if x + width > bin.originx + bin.width {
+            return None;
+        }
+
+        let mut level = 0;
+        let mut remaining = width;
+        for segment in &skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            level = i32::max(level, segment.level);
+            remaining -= segment.width;
+        }
+        let bottom = bin.originy + bin.height;
+        while let Some(obstacle) = bin.obstacles.iter().find(|o| {
+            let (ox1, ox2, oy1, oy2) = o.get_coords();
+            ox1 < x + width && x < ox2 && bottom - oy2 < level + height && level < bottom - oy1
+        }) {
+            level = bottom - obstacle.originy;
+        }
+        if level + height > bin.height {
+            return None;
+        }
+
+        let mut wasted = 0;
+        let mut remaining = width;
+        for segment in &skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            wasted += (level - segment.level) as i64 * i32::min(remaining, segment.width) as i64;
+            remaining -= segment.width;
+        }
+        Some((level, wasted))
+    }
+    /// Finds the best position for `box_item` on the skyline of any bin it allows, keeping
+    /// to the bin `home` when it is set.
+    fn best_fit(&self, box_item: &PackingBox, home: Option<i32>) -> Option<Candidate> {
+        self.bins
+            .iter()
+            .zip(&self.skylines)
+            .enumerate()
+            .filter(|(_, (bin, _))| {
+                box_item.allows(bin.bucketid) && home.is_none_or(|id| bin.bucketid == id)
+            })
+            .flat_map(|(b, (bin, skyline))| {
+                box_item
+                    .orientations(self.allow_rotation, bin.grain)
+                    .into_iter()
+                    .flat_map(move |(width, height, rotate)| {
+                        (0..skyline.len()).filter_map(move |s| {
+                            Self::fit(bin, skyline, s, width, height).map(|(level, wasted)| {
+                                let score = match self.rule {
+                                    SkylineRule::BottomLeft => {
+                                        ((level + height) as i64, skyline[s].width)
+                                    }
+                                    SkylineRule::MinWasteFit => (wasted, level + height),
+                                };
+                                (score, b, s, level, rotate)
+                            })
+                        })
+                    })
+            })
+            .min_by(|a, b| a.0.cmp(&b.0))
+    }
+    /// Raises the skyline of bin `b` to cover a `width` by `height` box resting at `level` on
+    /// the segment at `index`, handing any space trapped underneath to the waste map.
+    fn add_level(&mut self, b: usize, index: usize, level: i32, width: i32, height: i32) {
+        let bin = &self.bins[b];
+        let skyline = &mut self.skylines[b];
+        let x = skyline[index].x;
+
+        let i = index;
+        while i < skyline.len() && skyline[i].x < x + width {
+            let segment = skyline[i];
+            let covered = i32::min(segment.x + segment.width, x + width) - segment.x;
+            if self.use_waste_map && segment.level < level {
+                let trapped = Bucket::new(
+                    covered,
+                    level - segment.level,
+                    segment.x,
+                    bin.originy + bin.height - level,
+                    bin.bucketid,
+                );
+                self.waste.bins.extend(trapped.subtract(&bin.obstacles));
+            }
+            if covered < segment.width {
+                skyline[i].x += covered;
+                skyline[i].width -= covered;
+                break;
+            }
+            skyline.remove(i);
+        }
+        skyline.insert(
+            index,
+            Segment {
+                x,
+                width,
+                level: level + height,
+            },
+        );
+
+        let mut i = 0;
+        while i + 1 < skyline.len() {
+            if skyline[i].level == skyline[i + 1].level {
+                skyline[i].width += skyline[i + 1].width;
+                skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    /// Returns the free space above each skyline together with any space in the waste map.
+    fn free_space(&self) -> Vec<Bucket> {
+        let mut free = self.waste.bins.clone();
+        for (bin, skyline) in self.bins.iter().zip(&self.skylines) {
+            for segment in skyline.iter().filter(|s| s.level < bin.height) {
+                let above = Bucket::new(
+                    segment.width,
+                    bin.height - segment.level,
+                    segment.x,
+                    bin.originy,
+                    bin.bucketid,
+                );
+                free.extend(above.subtract(&bin.obstacles));
+            }
+        }
+        free
+    }
+    /// Attempts to place the boxes into the bins.
+    ///
+    /// Boxes are taken in the order given. Each one goes into the waste map if it fits there,
+    /// otherwise onto the best position along any skyline. Boxes sharing a `group` are placed
+    /// in the same bin or not at all.
+    ///
+    /// # Returns
+    /// A tuple of three vectors:
+    /// - A vector of `PackingBox` objects representing the placed boxes.
+    /// - A vector of `PackingBox` objects representing the remaining unplaced boxes.
+    /// - A vector of `Bucket` objects representing the free space left in the bins.
+    pub fn place(&mut self) -> (Vec<PackingBox>, Vec<PackingBox>, Vec<Bucket>) {
+        if !self.boxes.iter().any(|b| b.group.is_some()) {
+            return self.fill();
+        }
+        let start = self.clone();
+        let order = grouping::bin_order(&self.bins);
+        let (placed, unplaced, free) =
+            grouping::place_grouped(&start.boxes, &order, |boxes, pins| {
+                *self = start.clone();
+                self.boxes = boxes;
+                self.pins = pins.clone();
+                self.fill()
+            });
+        self.pins.clear();
+        self.boxes = unplaced.clone();
+        (placed, unplaced, free)
+    }
+    /// Places the boxes in order, sending each box that belongs to a group to its group's bin.
+    fn fill(&mut self) -> Packed {
+        let mut placed = vec![];
+        let mut unplaced = vec![];
+        let containers: HashMap<i32, Bucket> = self
+            .bins
+            .iter()
+            .map(|bin| (bin.bucketid, bin.clone()))
+            .collect();
+
+        for mut box_item in std::mem::take(&mut self.boxes) {
+            let home = grouping::home(&box_item, &placed, &self.pins);
+            if self.use_waste_map {
+                if let Some((_, idx, rotate)) = self.waste.best_fit(&box_item, &containers, home) {
+                    placed.push(self.waste.commit(box_item, idx, rotate));
+                    continue;
+                }
+            }
+
+            match self.best_fit(&box_item, home) {
+                Some((_, b, s, level, rotate)) => {
+                    if rotate {
+                        box_item.rotate();
+                    }
+                    let x = self.skylines[b][s].x;
+                    self.add_level(b, s, level, box_item.width, box_item.height);
+                    let bin = &self.bins[b];
+                    box_item.place(
+                        x,
+                        bin.originy + bin.height - level - box_item.height,
+                        bin.bucketid,
+                    );
+                    placed.push(box_item);
+                }
+                None => unplaced.push(box_item),
+            }
+        }
+
+        self.boxes = unplaced;
+        (placed, self.boxes.clone(), self.free_space())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_disjoint(placed: &[PackingBox], free: &[Bucket]) {
+        for (i, a) in placed.iter().enumerate() {
+            let rect = Bucket::new(
+                a.width,
+                a.height,
+                a.originx.unwrap(),
+                a.originy.unwrap(),
+                a.bucketid.unwrap(),
+            );
+            assert!(placed.iter().skip(i + 1).all(|b| !b.overlap(&rect)));
+            assert!(free.iter().all(|f| !a.overlap(f)));
+        }
+    }
+
+    #[test]
+    fn test_place_bottom_row() {
+        let boxes = vec![PackingBox::new(4, 3), PackingBox::new(6, 5)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut skyline = Skyline::new(boxes, bins);
+        let (placed, remaining, free) = skyline.place();
+
+        assert!(remaining.is_empty());
+        assert_eq!(placed[0].get_coords(), (0, 4, 7, 10));
+        assert_eq!(placed[1].get_coords(), (4, 10, 5, 10));
+        assert_eq!(free.len(), 2);
+        assert_disjoint(&placed, &free);
+    }
+
+    #[test]
+    fn test_place_stacks_on_lowest_segment() {
+        let boxes = vec![
+            PackingBox::new(4, 3),
+            PackingBox::new(6, 5),
+            PackingBox::new(4, 2),
+        ];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut skyline = Skyline::new(boxes, bins);
+        let (placed, _, _) = skyline.place();
+
+        assert_eq!(placed[2].get_coords(), (0, 4, 5, 7));
+    }
+
+    #[test]
+    fn test_place_no_fit() {
+        let boxes = vec![PackingBox::new(11, 3), PackingBox::new(3, 3)];
+        let bins = vec![Bucket::new(10, 10, 0, 0, 1)];
+        let mut skyline = Skyline::new(boxes, bins);
+        let (placed, remaining, _) = skyline.place();
+
+        assert_eq!(placed.len(), 1);
+        assert_eq!(remaining[0].width, 11);
+    }
+
+    #[test]
+    fn test_waste_map_reuses_trapped_space() {
+        // The wide box bridges the gap next to the short one, trapping a 6x2 hole under it.
+        let boxes = vec![
+            PackingBox::new(4, 4),
+            PackingBox::new(6, 2),
+            PackingBox::new(10, 2),
+            PackingBox::new(6, 2),
+        ];
+        let bins = vec![Bucket::new(10, 6, 0, 0, 1)];
+
+        let mut plain = Skyline::new(boxes.clone(), bins.clone());
+        let (placed, remaining, _) = plain.place();
+        assert_eq!(placed.len(), 3);
+        assert_eq!(remaining.len(), 1);
+
+        let mut waste = Skyline::new(boxes, bins).with_waste_map(true);
+        let (placed, remaining, free) = waste.place();
+        assert_eq!(placed.len(), 4);
+        assert!(remaining.is_empty());
+        assert_disjoint(&placed, &free);
+    }
+
+    #[test]
+    fn test_obstacles() {
+        let sheet = Bucket::new(10, 10, 0, 0, 1).with_obstacle(2, 2, 4, 4);
+        let obstacle = sheet.obstacles[0].clone();
+
+        let mut skyline = Skyline::new(vec![PackingBox::new(5, 5)], vec![sheet.clone()]);
+        assert!(skyline.place().0.is_empty());
+
+        for use_waste_map in [false, true] {
+            let boxes = vec![PackingBox::new(4, 4); 4];
+            let mut skyline =
+                Skyline::new(boxes, vec![sheet.clone()]).with_waste_map(use_waste_map);
+            let (placed, remaining, free) = skyline.place();
+
+            assert!(remaining.is_empty());
+            // The last box would rest on the obstacle, so it is lifted clear of it.
+            assert_eq!(placed[3].get_coords(), (4, 8, 0, 4));
+            assert!(placed.iter().all(|b| !b.overlap(&obstacle)));
+            assert!(free.iter().all(|f| !f.overlap(&obstacle)));
+            assert_disjoint(&placed, &free);
+        }
+    }
+
+    #[test]
+    fn test_allowed_bins() {
+        let boxes = vec![
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5).with_allowed_bins([1]),
+            PackingBox::new(5, 5),
+            PackingBox::new(5, 5).with_allowed_bins([2]),
+        ];
+        let bins = vec![Bucket::new(5, 5, 0, 0, 0), Bucket::new(5, 5, 0, 0, 1)];
+        let mut skyline = Skyline::new(boxes, bins);
+        let (placed, remaining, _) = skyline.place();
+
+        assert_eq!((placed.len(), remaining.len()), (2, 2));
+        assert!(placed.iter().all(|b| b.allows(b.bucketid.unwrap())));
+        assert!(remaining.iter().any(|b| b.allows(2) && !b.allows(1)));
+    }
+
+    #[test]
+    fn test_groups_share_a_bin() {
+        let mut boxes = vec![PackingBox::new(5, 5).with_group("a"); 3];
+        boxes.extend(vec![PackingBox::new(5, 5).with_group("b"); 2]);
+        boxes.push(PackingBox::new(5, 5));
+        boxes.push(PackingBox::new(5, 5).with_group("c"));
+        boxes.push(PackingBox::new(6, 6).with_group("c"));
+        let bins = vec![
+            Bucket::new(10, 5, 0, 0, 0),
+            Bucket::new(10, 10, 0, 0, 1),
+            Bucket::new(5, 5, 0, 0, 2),
+        ];
+        let mut skyline = Skyline::new(boxes, bins);
+        let (placed, remaining, _) = skyline.place();
+
+        assert_eq!(placed.len(), 6);
+        // Group "c" fits whole in no bin, so neither of its boxes is placed.
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().all(|b| b.group.as_deref() == Some("c")));
+        for group in ["a", "b"] {
+            let mut ids: Vec<_> = placed
+                .iter()
+                .filter(|b| b.group.as_deref() == Some(group))
+                .map(|b| b.bucketid)
+                .collect();
+            ids.dedup();
+            assert_eq!(ids.len(), 1, "{}", group);
+        }
+        assert!(skyline.pins.is_empty());
+    }
+
+    #[test]
+    fn test_rules_and_rotation() {
+        for rule in [SkylineRule::BottomLeft, SkylineRule::MinWasteFit] {
+            let boxes = vec![PackingBox::new(10, 2), PackingBox::new(2, 8)];
+            let bins = vec![Bucket::new(8, 10, 0, 0, 1)];
+            let mut skyline = Skyline::new(boxes, bins)
+                .with_rule(rule)
+                .with_rotation(true);
+            let (placed, remaining, free) = skyline.place();
+
+            assert!(remaining.is_empty(), "{:?}", rule);
+            assert!(placed[0].rotated);
+            assert_disjoint(&placed, &free);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! The `sort` module provides the pre-sorts applied to boxes before greedy packing, whose
+//! quality depends heavily on the order the boxes arrive in.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::packing_box::PackingBox;
+
+/// A user-supplied comparison between two boxes.
+pub type Comparator = Arc<dyn Fn(&PackingBox, &PackingBox) -> Ordering + Send + Sync>;
+
+/// The property boxes are sorted by.
+#[derive(Clone)]
+pub enum SortKey {
+    Area,
+    Perimeter,
+    /// The longer of the two sides.
+    MaxSide,
+    /// The shorter of the two sides.
+    MinSide,
+    Width,
+    Height,
+    /// The longer side divided by the shorter, so squares come first in ascending order.
+    Ratio,
+    /// What placing the box is worth, as returned by `PackingBox::value`.
+    Value,
+    /// `PackingBox::value` divided by the box's area, so boxes worth the most for the space
+    /// they take come first in descending order.
+    ValueDensity,
+    /// A user-supplied comparator. The name identifies it when the strategy is recorded.
+    Custom {
+        name: String,
+        compare: Comparator,
+    },
+}
+
+impl SortKey {
+    /// Compares two boxes by this key, smallest first.
+    fn compare(&self, a: &PackingBox, b: &PackingBox) -> Ordering {
+        let long = |b: &PackingBox| i32::max(b.width, b.height) as i64;
+        let short = |b: &PackingBox| i32::min(b.width, b.height) as i64;
+        let area = |b: &PackingBox| b.width as i64 * b.height as i64;
+        let perimeter = |b: &PackingBox| b.width as i64 + b.height as i64;
+
+        match self {
+            SortKey::Area => area(a).cmp(&area(b)),
+            SortKey::Perimeter => perimeter(a).cmp(&perimeter(b)),
+            SortKey::MaxSide => long(a).cmp(&long(b)),
+            SortKey::MinSide => short(a).cmp(&short(b)),
+            SortKey::Width => a.width.cmp(&b.width),
+            SortKey::Height => a.height.cmp(&b.height),
+            SortKey::Ratio => (long(a) * short(b)).cmp(&(long(b) * short(a))),
+            SortKey::Value => a.value().total_cmp(&b.value()),
+            SortKey::ValueDensity => {
+                (a.value() / area(a) as f64).total_cmp(&(b.value() / area(b) as f64))
+            }
+            SortKey::Custom { compare, .. } => compare(a, b),
+        }
+    }
+}
+
+impl fmt::Debug for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortKey::Area => write!(f, "Area"),
+            SortKey::Perimeter => write!(f, "Perimeter"),
+            SortKey::MaxSide => write!(f, "MaxSide"),
+            SortKey::MinSide => write!(f, "MinSide"),
+            SortKey::Width => write!(f, "Width"),
+            SortKey::Height => write!(f, "Height"),
+            SortKey::Ratio => write!(f, "Ratio"),
+            SortKey::Value => write!(f, "Value"),
+            SortKey::ValueDensity => write!(f, "ValueDensity"),
+            SortKey::Custom { name, .. } => f.debug_tuple("Custom").field(name).finish(),
+        }
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                SortKey::Custom { name, compare },
+                SortKey::Custom {
+                    name: other_name,
+                    compare: other_compare,
+                },
+            ) => name == other_name && Arc::ptr_eq(compare, other_compare),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+/// Whether boxes are sorted smallest or largest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+/// A pre-sort: the key to sort by and the direction.
+///
+/// Sorting is stable, so boxes that compare equal keep their input order.
+///
+/// # Examples
+/// ```
+/// use max_rects::{packing_box::PackingBox, sort::{SortKey, SortStrategy}};
+///
+/// let mut boxes = vec![PackingBox::new(2, 2), PackingBox::new(5, 1), PackingBox::new(3, 3)];
+/// SortStrategy::descending(SortKey::MaxSide).apply(&mut boxes);
+/// assert_eq!(boxes[0].width, 5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortStrategy {
+    pub key: SortKey,
+    pub direction: Direction,
+}
+
+impl SortStrategy {
+    /// Sorts by `key`, smallest first.
+    pub fn ascending(key: SortKey) -> Self {
+        Self {
+            key,
+            direction: Direction::Ascending,
+        }
+    }
+    /// Sorts by `key`, largest first.
+    pub fn descending(key: SortKey) -> Self {
+        Self {
+            key,
+            direction: Direction::Descending,
+        }
+    }
+    /// Sorts with a user-supplied comparator, in the order it defines.
+    pub fn custom<F>(name: &str, compare: F) -> Self
+    where
+        F: Fn(&PackingBox, &PackingBox) -> Ordering + Send + Sync + 'static,
+    {
+        Self::ascending(SortKey::Custom {
+            name: name.to_string(),
+            compare: Arc::new(compare),
+        })
+    }
+    /// Compares two boxes, returning `Less` if `a` should come first.
+    pub fn compare(&self, a: &PackingBox, b: &PackingBox) -> Ordering {
+        match self.direction {
+            Direction::Ascending => self.key.compare(a, b),
+            Direction::Descending => self.key.compare(b, a),
+        }
+    }
+    /// Reorders `boxes` in place.
+    pub fn apply(&self, boxes: &mut [PackingBox]) {
+        boxes.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+impl Default for SortStrategy {
+    /// Largest area first.
+    fn default() -> Self {
+        Self::descending(SortKey::Area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes() -> Vec<PackingBox> {
+        vec![
+            PackingBox::new(4, 1),
+            PackingBox::new(2, 3),
+            PackingBox::new(5, 5),
+            PackingBox::new(1, 6),
+        ]
+    }
+
+    fn sizes(boxes: &[PackingBox]) -> Vec<(i32, i32)> {
+        boxes.iter().map(|b| (b.width, b.height)).collect()
+    }
+
+    fn sorted(strategy: SortStrategy) -> Vec<(i32, i32)> {
+        let mut boxes = boxes();
+        strategy.apply(&mut boxes);
+        sizes(&boxes)
+    }
+
+    #[test]
+    fn test_keys() {
+        use SortKey::*;
+        let ascending = |key| sorted(SortStrategy::ascending(key));
+
+        assert_eq!(ascending(Area), vec![(4, 1), (2, 3), (1, 6), (5, 5)]);
+        assert_eq!(ascending(Perimeter), vec![(4, 1), (2, 3), (1, 6), (5, 5)]);
+        assert_eq!(ascending(MaxSide), vec![(2, 3), (4, 1), (5, 5), (1, 6)]);
+        assert_eq!(ascending(MinSide), vec![(4, 1), (1, 6), (2, 3), (5, 5)]);
+        assert_eq!(ascending(Width), vec![(1, 6), (2, 3), (4, 1), (5, 5)]);
+        assert_eq!(ascending(Height), vec![(4, 1), (2, 3), (5, 5), (1, 6)]);
+        assert_eq!(ascending(Ratio), vec![(5, 5), (2, 3), (4, 1), (1, 6)]);
+        assert_eq!(ascending(Value), ascending(Area));
+        // Every box is worth its area by default, so the input order is kept.
+        assert_eq!(ascending(ValueDensity), sizes(&boxes()));
+
+        let mut boxes = vec![
+            PackingBox::new(10, 10).with_value(10.0),
+            PackingBox::new(5, 10).with_value(6.0),
+            PackingBox::new(1, 1).with_value(0.5),
+        ];
+        SortStrategy::descending(ValueDensity).apply(&mut boxes);
+        assert_eq!(sizes(&boxes), vec![(1, 1), (5, 10), (10, 10)]);
+    }
+
+    #[test]
+    fn test_large_boxes() {
+        let mut boxes = vec![PackingBox::new(i32::MAX, 2), PackingBox::new(i32::MAX, 1)];
+        SortStrategy::ascending(SortKey::Area).apply(&mut boxes);
+        assert_eq!(boxes[0].height, 1);
+        SortStrategy::descending(SortKey::Perimeter).apply(&mut boxes);
+        assert_eq!(boxes[0].height, 2);
+    }
+
+    #[test]
+    fn test_descending_is_stable() {
+        let mut boxes = vec![
+            PackingBox::new(2, 3),
+            PackingBox::new(3, 2),
+            PackingBox::new(1, 1),
+        ];
+        boxes[1].originx = Some(7);
+        SortStrategy::descending(SortKey::Area).apply(&mut boxes);
+
+        assert_eq!(sizes(&boxes), vec![(2, 3), (3, 2), (1, 1)]);
+        assert_eq!(boxes[1].originx, Some(7));
+    }
+
+    #[test]
+    fn test_custom() {
+        let by_height_then_width =
+            |a: &PackingBox, b: &PackingBox| (a.height, a.width).cmp(&(b.height, b.width));
+        let strategy = SortStrategy::custom("height then width", by_height_then_width);
+
+        assert_eq!(
+            sorted(strategy.clone()),
+            vec![(4, 1), (2, 3), (5, 5), (1, 6)]
+        );
+        assert_eq!(
+            format!("{:?}", strategy.key),
+            "Custom(\"height then width\")"
+        );
+        assert_eq!(strategy, strategy.clone());
+
+        let descending = SortStrategy {
+            direction: Direction::Descending,
+            ..strategy
+        };
+        assert_eq!(sorted(descending), vec![(1, 6), (5, 5), (2, 3), (4, 1)]);
+    }
+
+    #[test]
+    fn test_equality() {
+        assert_eq!(
+            SortStrategy::default(),
+            SortStrategy::descending(SortKey::Area)
+        );
+        assert_ne!(
+            SortStrategy::ascending(SortKey::Width),
+            SortStrategy::descending(SortKey::Width)
+        );
+        assert_ne!(SortKey::Width, SortKey::Height);
+    }
+}
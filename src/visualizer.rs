@@ -33,6 +33,7 @@ use crate::packing_box::PackingBox;
 /// // generate_visualization(&placed_boxes, &bins);
 /// // This will generate an image named 'output.png' visualizing the packed bins and boxes.
 /// ```
+#[allow(clippy::ptr_arg)]
 pub fn generate_visualization(placed_boxes: &[PackingBox], bins: &Vec<Bucket>) {
     const BUFFER: i32 = 10; // Define a buffer of 10 pixels between bins
 
@@ -55,6 +56,17 @@ pub fn generate_visualization(placed_boxes: &[PackingBox], bins: &Vec<Bucket>) {
             Rgb([200, 200, 200]),
         );
 
+        // Draw the obstacles with a dark gray color
+        for obstacle in &bin.obstacles {
+            let (x1, x2, y1, y2) = obstacle.get_coords();
+            draw_filled_rect_mut(
+                &mut img,
+                Rect::at(x1 + i as i32 * (max_bin_width + BUFFER), y1)
+                    .of_size((x2 - x1) as u32, (y2 - y1) as u32),
+                Rgb([80, 80, 80]),
+            );
+        }
+
         for box_item in placed_boxes
             .iter()
             .filter(|&b| b.bucketid == Some(bin.bucketid))